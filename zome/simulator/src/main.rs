@@ -0,0 +1,99 @@
+//! Runs simulated sessions of the game offline, with bots on every seat.
+//!
+//! Usage: simulator <config.json> [--csv <file>] [--json <file>]
+//!        simulator --metrics <export.json>
+//!
+//! Without any output option the per-round CSV is written to stdout.
+//! With --metrics it prints the metrics of a session exported with export_session instead.
+
+mod simulation;
+
+use simulation::{run, RoundRecord, SimulationConfig};
+use std::{env, fs, process};
+use tragedy_of_commons::{export::SessionExport, metrics::session_metrics};
+
+const USAGE: &str = "Usage: simulator <config.json> [--csv <file>] [--json <file>]\n       simulator --metrics <export.json>";
+
+struct Args {
+    config: Option<String>,
+    csv: Option<String>,
+    json: Option<String>,
+    metrics: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut config: Option<String> = None;
+    let mut csv: Option<String> = None;
+    let mut json: Option<String> = None;
+    let mut metrics: Option<String> = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--csv" => csv = Some(args.next().ok_or("--csv needs a file name")?),
+            "--json" => json = Some(args.next().ok_or("--json needs a file name")?),
+            "--metrics" => metrics = Some(args.next().ok_or("--metrics needs a file name")?),
+            _ if config.is_none() && !arg.starts_with("--") => config = Some(arg),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+    if config.is_none() && metrics.is_none() {
+        return Err("Missing config file".into());
+    }
+    Ok(Args {
+        config,
+        csv,
+        json,
+        metrics,
+    })
+}
+
+fn to_csv(records: &[RoundRecord]) -> String {
+    let mut csv = String::from(RoundRecord::CSV_HEADER);
+    csv.push('\n');
+    for record in records {
+        csv.push_str(&record.to_csv());
+        csv.push('\n');
+    }
+    csv
+}
+
+fn print_metrics(path: &str) -> Result<(), String> {
+    let export = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    let export: SessionExport = serde_json::from_str(&export).map_err(|e| format!("Invalid export: {}", e))?;
+    let metrics = serde_json::to_string_pretty(&session_metrics(&export)).map_err(|e| e.to_string())?;
+    println!("{}", metrics);
+    Ok(())
+}
+
+fn simulate(config_path: &str, args: Args) -> Result<(), String> {
+    let config = fs::read_to_string(config_path).map_err(|e| format!("Cannot read {}: {}", config_path, e))?;
+    let config: SimulationConfig = serde_json::from_str(&config).map_err(|e| format!("Invalid config: {}", e))?;
+    if config.bots.is_empty() {
+        return Err("Config needs at least one bot".into());
+    }
+    config.params.validate()?;
+
+    let records = run(&config);
+    if args.csv.is_none() && args.json.is_none() {
+        print!("{}", to_csv(&records));
+    }
+    if let Some(path) = args.csv {
+        fs::write(&path, to_csv(&records)).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+    }
+    if let Some(path) = args.json {
+        let json = serde_json::to_string_pretty(&records).map_err(|e| e.to_string())?;
+        fs::write(&path, json).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let result = parse_args(env::args().skip(1)).and_then(|args| match (args.metrics.clone(), args.config.clone()) {
+        (Some(path), _) => print_metrics(&path),
+        (None, Some(config_path)) => simulate(&config_path, args),
+        (None, None) => Err("Missing config file".into()),
+    });
+    if let Err(e) = result {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,174 @@
+use hdk::prelude::*;
+use holo_hash::{AgentPubKey, AgentPubKeyB64, EntryHash};
+use tragedy_of_commons::{
+    bots::{BotContext, BotStrategy},
+    fixed_point::Fixed,
+    game_move::GameMove,
+    game_round::calculate_round_state,
+    game_session::GameParams,
+    quota_vote::{decide_quota, QuotaVote},
+    random::SeededRng,
+    types::{new_player_stats, ResourceAmount, ResourcePools},
+};
+
+/// What to simulate, read from the JSON config file. Amounts in params are
+/// in thousandths, just like the UI sends them to the zome.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// how many sessions to run with the same params
+    pub sessions: u32,
+    /// seed of the first session, every session and round derives its own numbers from it
+    #[serde(default)]
+    pub seed: u64,
+    pub params: GameParams,
+    /// one bot per seat, the number of players is the length of this list
+    pub bots: Vec<BotStrategy>,
+}
+
+/// State of one resource pool at the end of a round
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoundRecord {
+    pub session: u32,
+    pub round: u32,
+    pub resource: String,
+    pub at_start: ResourceAmount,
+    pub taken: ResourceAmount,
+    /// what the next round starts with, after regrowth
+    pub left: ResourceAmount,
+    pub quota: Option<ResourceAmount>,
+    pub shock: bool,
+}
+
+impl RoundRecord {
+    pub const CSV_HEADER: &'static str = "session,round,resource,at_start,taken,left,quota,shock";
+
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{}",
+            self.session,
+            self.round,
+            self.resource,
+            self.at_start,
+            self.taken,
+            self.left,
+            self.quota.map(|q| q.to_string()).unwrap_or_default(),
+            self.shock,
+        )
+    }
+}
+
+// NOTE: follows what the zome does when a round is closed, but without any DHT:
+// bots vote and move, the shock is drawn and calculate_round_state does the rest.
+// Bots never sanction, so the sanction phase doesn't change anything and is skipped.
+/// Plays one session with bots on every seat and returns a record per round and resource pool
+pub fn run_session(config: &SimulationConfig, session: u32) -> Vec<RoundRecord> {
+    let params = &config.params;
+    let players: Vec<AgentPubKey> = (0..config.bots.len()).map(|seat| fake_agent(seat as u32)).collect();
+    let mut resources = params.start_resources();
+    let mut stats = new_player_stats(
+        players.iter().cloned().map(AgentPubKeyB64::from).collect(),
+        params.resource_names(),
+    );
+    let mut records: Vec<RoundRecord> = vec![];
+
+    for round_num in 1..=params.num_rounds {
+        let round = fake_round(session, round_num);
+        let context = |quota: Option<&ResourcePools>| BotContext {
+            params,
+            round_num,
+            resources_left: &resources,
+            previous_stats: &stats,
+            quota,
+            seats: players.len(),
+        };
+
+        let quota = params.quota_voting.map(|rule| {
+            let votes: Vec<QuotaVote> = players
+                .iter()
+                .zip(config.bots.iter())
+                .enumerate()
+                .map(|(seat, (owner, strategy))| QuotaVote {
+                    owner: owner.clone(),
+                    round: round.clone(),
+                    caps: strategy.plan_move(&context(None), &mut seat_rng(config.seed, session, round_num, seat)),
+                })
+                .collect();
+            decide_quota(rule, params.resource_names(), &votes)
+        });
+
+        let moves: Vec<GameMove> = players
+            .iter()
+            .zip(config.bots.iter())
+            .enumerate()
+            .map(|(seat, (owner, strategy))| GameMove {
+                owner: owner.clone(),
+                round: round.clone(),
+                resources: strategy.plan_move(&context(quota.as_ref()), &mut seat_rng(config.seed, session, round_num, seat)),
+                quota_header_hash: None,
+            })
+            .collect();
+        let taken: ResourcePools = params
+            .resources
+            .iter()
+            .map(|pool| (pool.name.clone(), moves.iter().map(|m| m.amount_of(&pool.name)).sum()))
+            .collect();
+
+        let mut shock_rng = SeededRng::from_bytes(&[&config.seed.to_le_bytes(), &session.to_le_bytes(), &round_num.to_le_bytes()]);
+        let shock = params.shocks.and_then(|shocks| shocks.draw(&mut shock_rng));
+        let (resources_left, round_stats) = calculate_round_state(params, &resources, &stats, moves, shock);
+
+        for pool in params.resources.iter() {
+            records.push(RoundRecord {
+                session,
+                round: round_num,
+                resource: pool.name.clone(),
+                at_start: resources.get(&pool.name).copied().unwrap_or(Fixed::ZERO),
+                taken: taken.get(&pool.name).copied().unwrap_or(Fixed::ZERO),
+                left: resources_left.get(&pool.name).copied().unwrap_or(Fixed::ZERO),
+                quota: quota.as_ref().and_then(|q| q.get(&pool.name).copied()),
+                shock: shock.is_some(),
+            });
+        }
+
+        resources = resources_left;
+        stats = round_stats;
+        // same rule as the zome: the game is over as soon as one pool is depleted
+        if !resources.values().all(|amount| amount.is_positive()) {
+            break;
+        }
+    }
+    records
+}
+
+/// Runs all sessions of the config, session numbers start at 1
+pub fn run(config: &SimulationConfig) -> Vec<RoundRecord> {
+    (1..=config.sessions)
+        .flat_map(|session| run_session(config, session))
+        .collect()
+}
+
+fn seat_rng(seed: u64, session: u32, round_num: u32, seat: usize) -> SeededRng {
+    SeededRng::from_bytes(&[
+        &seed.to_le_bytes(),
+        &session.to_le_bytes(),
+        &round_num.to_le_bytes(),
+        &(seat as u64).to_le_bytes(),
+    ])
+}
+
+// the engine only compares keys and hashes, so they don't need to be real
+fn fake_agent(seat: u32) -> AgentPubKey {
+    AgentPubKey::from_raw_36(fake_hash_bytes(&[seat, 0]))
+}
+
+fn fake_round(session: u32, round_num: u32) -> EntryHash {
+    EntryHash::from_raw_36(fake_hash_bytes(&[session, round_num]))
+}
+
+fn fake_hash_bytes(parts: &[u32]) -> Vec<u8> {
+    let mut bytes = vec![0u8; 36];
+    for (i, part) in parts.iter().enumerate() {
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&part.to_le_bytes());
+    }
+    bytes
+}
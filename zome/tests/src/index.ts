@@ -121,19 +121,28 @@ orchestrator.registerScenario(
     console.log("current_round_hash", current_round_hash);
 
     // ROUND 1
+    // resource amounts are fixed-point thousandths: 5000 == 5.000
     // Alice makes 1 move
     let game_move_round_1_alice = await alice_common.cells[0].call(
       ZOME_NAME,
       "new_move",
-      {resource_amount: 5, current_round_header_hash: current_round_hash},
+      {resources: {commons: 5000}, current_round_header_hash: current_round_hash},
     );
 
     // Bob makes 1 move
     let game_move_round_1_bob = await bob_common.cells[0].call(
       ZOME_NAME,
       "new_move",
-      {resource_amount: 10, current_round_header_hash: current_round_hash},
+      {resources: {commons: 10000}, current_round_header_hash: current_round_hash},
     );
+    // Bob changes his mind before the round is closed
+    let game_move_round_1_bob_updated = await bob_common.cells[0].call(
+      ZOME_NAME,
+      "update_move",
+      {resources: {commons: 8000}, current_round_header_hash: current_round_hash},
+    );
+    t.ok(game_move_round_1_bob_updated);
+
     console.log(game_move_round_1_alice);
     t.ok(game_move_round_1_alice);
     console.log(game_move_round_1_bob);
@@ -179,7 +188,7 @@ orchestrator.registerScenario(
     let game_move_round_2_bob = await bob_common.cells[0].call(
       ZOME_NAME,
       "new_move",
-      {resource_amount: 15, current_round_header_hash: round_2_header_hash},
+      {resources: {commons: 15000}, current_round_header_hash: round_2_header_hash},
     );
 
     // console.log(game_move_round_2_bob);
@@ -195,7 +204,7 @@ orchestrator.registerScenario(
     // let game_move_round_2_alice = await alice_common.cells[0].call(
     //   ZOME_NAME,
     //   "new_move",
-    //   {resource_amount: 5, current_round_header_hash: round_2_header_hash},
+    //   {resources: {commons: 5000}, current_round_header_hash: round_2_header_hash},
     // );
     // console.log(game_move_round_2_alice);
     // t.ok(game_move_round_2_alice);
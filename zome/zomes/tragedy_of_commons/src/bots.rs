@@ -0,0 +1,244 @@
+use crate::{
+    fixed_point::Fixed,
+    game_move::{new_move_for, GameMove, GameMoveInput},
+    game_round::{try_to_close_round, ClosingPolicy, GameRound, RoundState},
+    game_sanction::{get_round_sanctions, new_sanction_for, GameSanctionInput},
+    game_session::{GameParams, GameSession},
+    quota_vote::{get_round_votes, new_quota_vote_for, QuotaVoteInput},
+    random::SeededRng,
+    types::{PlayerStats, ResourcePools},
+    utils::{entry_hash_from_element, try_from_element, try_get_by_header_and_convert, try_get_latest_element},
+};
+use hdk::prelude::*;
+use holo_hash::*;
+
+/// Strategies a bot seat can play with
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BotStrategy {
+    /// takes an equal share of everything that is left in every pool
+    AlwaysGreedy,
+    /// takes an equal share of what the pools would regrow if nobody took anything
+    SustainableShare,
+    /// takes what players took on average in the previous round,
+    /// and the sustainable share in the first round
+    TitForTat,
+    /// takes a random amount between nothing and the greedy amount
+    Random,
+}
+
+/// A seat of the session that is played by a bot. The agent has to be one of the players,
+/// the moves of the seat are made by the agent that may_play_bots, see play_bot_seats.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BotSeat {
+    pub agent: AgentPubKeyB64,
+    pub strategy: BotStrategy,
+}
+
+/// What a bot knows when it makes a move
+pub struct BotContext<'a> {
+    pub params: &'a GameParams,
+    pub round_num: u32,
+    pub resources_left: &'a ResourcePools,
+    /// stats the round starts with, their last_taken is what players took in the previous round
+    pub previous_stats: &'a PlayerStats,
+    pub quota: Option<&'a ResourcePools>,
+    pub seats: usize,
+}
+
+impl BotStrategy {
+    /// Amounts the bot takes from every resource pool
+    pub fn plan_move(&self, context: &BotContext, rng: &mut SeededRng) -> ResourcePools {
+        let seats = Fixed::from_int(std::cmp::max(context.seats, 1) as i64);
+        let mut planned = ResourcePools::new();
+        for pool in context.params.resources.iter() {
+            let available = std::cmp::max(
+                context.resources_left.get(&pool.name).copied().unwrap_or(Fixed::ZERO),
+                Fixed::ZERO,
+            );
+            let greedy = available.checked_div(seats).unwrap_or(Fixed::ZERO);
+            let sustainable = (pool.regeneration.regenerate(available) - available)
+                .checked_div(seats)
+                .unwrap_or(Fixed::ZERO);
+            let amount = match self {
+                BotStrategy::AlwaysGreedy => greedy,
+                BotStrategy::SustainableShare => sustainable,
+                BotStrategy::TitForTat => {
+                    let taken: Vec<Fixed> = context
+                        .previous_stats
+                        .values()
+                        .map(|stat| stat.last_taken.get(&pool.name).copied().unwrap_or(Fixed::ZERO))
+                        .collect();
+                    if context.round_num <= 1 || taken.is_empty() {
+                        sustainable
+                    } else {
+                        let total: Fixed = taken.iter().sum();
+                        total
+                            .checked_div(Fixed::from_int(taken.len() as i64))
+                            .unwrap_or(Fixed::ZERO)
+                    }
+                }
+                BotStrategy::Random => greedy * rng.next_unit(),
+            };
+            let cap = context
+                .quota
+                .and_then(|quota| quota.get(&pool.name).copied())
+                .unwrap_or(available);
+            planned.insert(pool.name.clone(), std::cmp::max(std::cmp::min(amount, cap), Fixed::ZERO));
+        }
+        planned
+    }
+}
+
+/// Bot seat of the session the given agent sits in, if it is played by a bot
+pub fn bot_seat<'a>(game_session: &'a GameSession, owner: &AgentPubKey) -> Option<&'a BotSeat> {
+    let seat = AgentPubKeyB64::from(owner.clone());
+    game_session.bots.iter().find(|b| b.agent == seat)
+}
+
+/// Whether agent plays the bot seats of the given round: the session owner, or with the
+/// Rotating policy the player that closes the round or the one before it, since that is
+/// the player that starts it. With the other policies the owner plays the bots even if
+/// somebody else started the phase, see play_bots.
+pub fn may_play_bots(game_session: &GameSession, round_num: u32, agent: &AgentPubKeyB64) -> bool {
+    if &game_session.owner == agent {
+        return true;
+    }
+    match game_session.game_params.closing_policy {
+        ClosingPolicy::Rotating => {
+            ClosingPolicy::rotating_closer(game_session, round_num) == Some(agent)
+                || (round_num > 1 && ClosingPolicy::rotating_closer(game_session, round_num - 1) == Some(agent))
+        }
+        _ => false,
+    }
+}
+
+/// Whether author may write the move, vote or sanction of the seat owner in the given round.
+/// Every seat plays for itself, bot seats are played by the agent that may_play_bots.
+pub fn may_play_for(game_session: &GameSession, round_num: u32, owner: &AgentPubKey, author: &AgentPubKey) -> bool {
+    if owner == author {
+        return true;
+    }
+    bot_seat(game_session, owner).is_some()
+        && may_play_bots(game_session, round_num, &AgentPubKeyB64::from(author.clone()))
+}
+
+/// Amounts a bot seat takes in the round, or votes for as caps when quota is None.
+/// The numbers are drawn from the round entry and the seat, so validation can recompute
+/// them and every bot draws its own numbers for every round.
+pub fn bot_plan(
+    game_session: &GameSession,
+    seat: &BotSeat,
+    round: &GameRound,
+    round_entry_hash: &EntryHash,
+    quota: Option<&ResourcePools>,
+) -> ResourcePools {
+    let context = BotContext {
+        params: &game_session.game_params,
+        round_num: round.round_num,
+        resources_left: &round.resources_left,
+        previous_stats: &round.player_stats,
+        quota,
+        seats: game_session.players.len(),
+    };
+    let owner = AgentPubKey::from(seat.agent.clone());
+    let mut rng = SeededRng::from_bytes(&[round_entry_hash.get_raw_32(), owner.get_raw_32()]);
+    seat.strategy.plan_move(&context, &mut rng)
+}
+
+/// Seats that already voted, moved or sanctioned in the phase the round is at
+fn acted_seats(round_state: &RoundState, round_entry_hash: EntryHash) -> ExternResult<Vec<AgentPubKey>> {
+    match round_state {
+        RoundState::Voting => Ok(get_round_votes(round_entry_hash)?.0.into_iter().map(|v| v.owner).collect()),
+        RoundState::InProgress => {
+            let links = get_links(round_entry_hash, Some(LinkTag::new("game_move")))?;
+            let mut owners = vec![];
+            for link in links.into_inner() {
+                let game_move: GameMove = match get(link.target, GetOptions::content())? {
+                    Some(element) => try_from_element(element)?,
+                    None => return Err(WasmError::Guest("Cannot extract game move from link".into())),
+                };
+                owners.push(game_move.owner);
+            }
+            Ok(owners)
+        }
+        RoundState::Sanctioning => Ok(get_round_sanctions(round_entry_hash)?.0.into_iter().map(|s| s.owner).collect()),
+        RoundState::Finished => Ok(vec![]),
+    }
+}
+
+/// Plays the bot seats that haven't acted yet in the phase the round is at: vote, move or
+/// (empty) sanction. Does nothing unless the caller may_play_bots. Returns whether all
+/// players have acted now and the caller may close the phase.
+pub fn play_bot_seats(round_header_hash: HeaderHash) -> ExternResult<bool> {
+    let round_element = match get(round_header_hash.clone(), GetOptions::content())? {
+        Some(element) => element,
+        None => return Err(WasmError::Guest("Current round not found".into())),
+    };
+    let round_entry_hash = entry_hash_from_element(round_element.clone())?;
+    let round: GameRound = try_from_element(round_element)?;
+    let game_session: GameSession = try_get_by_header_and_convert(round.session_header_hash.clone().into())?;
+    let me = AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey);
+    if game_session.bots.is_empty() || !may_play_bots(&game_session, round.round_num, &me) {
+        return Ok(false);
+    }
+    let latest_round: GameRound = try_from_element(try_get_latest_element(round_entry_hash.clone())?)?;
+    if latest_round.round_state == RoundState::Finished {
+        return Ok(false);
+    }
+
+    let mut acted = acted_seats(&latest_round.round_state, round_entry_hash.clone())?;
+    for seat in game_session.bots.iter() {
+        let owner = AgentPubKey::from(seat.agent.clone());
+        if acted.contains(&owner) {
+            continue;
+        }
+        let planned = bot_plan(&game_session, seat, &round, &round_entry_hash, latest_round.quota.as_ref());
+        debug!("bot {:?} plays {:?}", seat, planned);
+        match latest_round.round_state {
+            RoundState::Voting => {
+                new_quota_vote_for(owner.clone(), QuotaVoteInput {
+                    caps: planned,
+                    current_round_header_hash: round_header_hash.clone().into(),
+                })?;
+            }
+            RoundState::InProgress => {
+                new_move_for(owner.clone(), GameMoveInput {
+                    resources: planned,
+                    current_round_header_hash: round_header_hash.clone().into(),
+                })?;
+            }
+            RoundState::Sanctioning => {
+                new_sanction_for(owner.clone(), GameSanctionInput {
+                    sanctions: vec![],
+                    current_round_header_hash: round_header_hash.clone().into(),
+                })?;
+            }
+            RoundState::Finished => {}
+        }
+        acted.push(owner);
+    }
+    Ok(acted.len() >= game_session.players.len()
+        && game_session.game_params.closing_policy.may_close(&game_session, round.round_num, &me))
+}
+
+/// Plays the bot seats of the round and closes the phase when that completes it.
+/// try_to_close_round goes on with the phases that follow, so a session with only
+/// bots is played to the end without waiting for the UI.
+pub fn run_bot_seats(round_header_hash: HeaderHash) -> ExternResult<()> {
+    if play_bot_seats(round_header_hash.clone())? {
+        try_to_close_round(round_header_hash)?;
+    }
+    Ok(())
+}
+
+/// Lets the session owner play the bot seats of a phase that was started by an agent
+/// that doesn't play them
+pub fn play_bots(round_header_hash: HeaderHash) -> ExternResult<()> {
+    let round: GameRound = try_get_by_header_and_convert(round_header_hash.clone())?;
+    let game_session: GameSession = try_get_by_header_and_convert(round.session_header_hash.clone().into())?;
+    let me = AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey);
+    if !may_play_bots(&game_session, round.round_num, &me) {
+        return Err(WasmError::Guest("Cannot play bots: only the owner of the session plays them".into()));
+    }
+    run_bot_seats(round_header_hash)
+}
@@ -0,0 +1,144 @@
+use crate::{
+    game_round::GameRound,
+    game_session::{GameSession, GameSignal},
+    spectators::signal_recipients,
+    utils::{entry_hash_from_element, try_from_element, try_get_by_header_and_convert},
+};
+use hdk::prelude::*;
+use holo_hash::*;
+use std::time::Duration;
+
+const MAX_MESSAGE_LENGTH: usize = 1000;
+
+/// "Cheap talk" between the players of a session
+#[hdk_entry(id = "chat_message", visibility = "public")]
+#[derive(Clone)]
+pub struct ChatMessage {
+    pub author: AgentPubKeyB64,
+    pub session_header_hash: HeaderHashB64,
+    /// round the message was sent in, None for messages about the session as a whole
+    pub round_header_hash: Option<HeaderHashB64>,
+    pub content: String,
+    // also keeps two identical messages from ending up as the same entry
+    pub sent_at: Duration,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct ChatMessageInput {
+    pub session_header_hash: HeaderHashB64,
+    pub round_header_hash: Option<HeaderHashB64>,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct ChatFilterInput {
+    pub session_header_hash: HeaderHashB64,
+    pub round_header_hash: Option<HeaderHashB64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct SignalPayloadChatMessage {
+    pub message: ChatMessage,
+    pub message_header_hash: HeaderHashB64,
+}
+
+/// Messages are linked from the round they were sent in, or from the session
+/// when no round is given. All players and spectators get the message as a signal.
+#[hdk_extern]
+pub fn send_chat_message(input: ChatMessageInput) -> ExternResult<HeaderHashB64> {
+    let game_session: GameSession = try_get_by_header_and_convert(input.session_header_hash.clone().into())?;
+    if !game_session.game_params.chat_enabled {
+        return Err(WasmError::Guest("Chat is not enabled for this session".into()));
+    }
+    if let Some(round_header_hash) = input.round_header_hash.clone() {
+        let game_round: GameRound = try_get_by_header_and_convert(round_header_hash.into())?;
+        if game_round.session_header_hash != input.session_header_hash {
+            return Err(WasmError::Guest("Round doesn't belong to this session".into()));
+        }
+    }
+
+    let message = ChatMessage {
+        author: AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey),
+        session_header_hash: input.session_header_hash.clone(),
+        round_header_hash: input.round_header_hash.clone(),
+        content: input.content,
+        sent_at: sys_time()?,
+    };
+    let message_header_hash = create_entry(&message)?;
+    let message_entry_hash = hash_entry(&message)?;
+    create_link(
+        chat_base(input.session_header_hash, input.round_header_hash)?,
+        message_entry_hash,
+        LinkTag::new("chat_message"),
+    )?;
+
+    let signal_payload = SignalPayloadChatMessage {
+        message,
+        message_header_hash: message_header_hash.clone().into(),
+    };
+    let signal = ExternIO::encode(GameSignal::ChatMessage(signal_payload))?;
+    remote_signal(signal, signal_recipients(&game_session)?)?;
+
+    Ok(message_header_hash.into())
+}
+
+/// Lists the messages of a session or of one of its rounds, oldest first
+#[hdk_extern]
+pub fn get_chat_messages(input: ChatFilterInput) -> ExternResult<Vec<ChatMessage>> {
+    let links = get_links(
+        chat_base(input.session_header_hash, input.round_header_hash)?,
+        Some(LinkTag::new("chat_message")),
+    )?;
+    let mut messages: Vec<ChatMessage> = vec![];
+    for link in links.into_inner() {
+        if let Some(element) = get(link.target, GetOptions::default())? {
+            messages.push(try_from_element(element)?);
+        }
+    }
+    messages.sort_by_key(|m| m.sent_at);
+    Ok(messages)
+}
+
+/// Only players of a session with chat enabled can send messages, and only in their own name.
+/// A round message has to be sent in a round of the session.
+#[hdk_extern]
+pub fn validate_create_entry_chat_message(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let message: ChatMessage = try_from_element(data.element.clone())?;
+    if data.element.header().author() != &AgentPubKey::from(message.author.clone()) {
+        return Ok(ValidateCallbackResult::Invalid("A message can only be sent by its author".into()));
+    }
+    if message.content.trim().is_empty() || message.content.chars().count() > MAX_MESSAGE_LENGTH {
+        return Ok(ValidateCallbackResult::Invalid("A message has to have between 1 and 1000 characters".into()));
+    }
+    if let Some(round_header_hash) = message.round_header_hash.clone() {
+        let round_header_hash: HeaderHash = round_header_hash.into();
+        let game_round: GameRound = match get(round_header_hash.clone(), GetOptions::content())? {
+            Some(element) => try_from_element(element)?,
+            None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![round_header_hash.into()])),
+        };
+        if game_round.session_header_hash != message.session_header_hash {
+            return Ok(ValidateCallbackResult::Invalid("Round doesn't belong to the session of the message".into()));
+        }
+    }
+    let session_header_hash: HeaderHash = message.session_header_hash.into();
+    let game_session: GameSession = match get(session_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![session_header_hash.into()])),
+    };
+    if !game_session.game_params.chat_enabled {
+        return Ok(ValidateCallbackResult::Invalid("Chat is not enabled for this session".into()));
+    }
+    if !game_session.players.contains(&message.author) {
+        return Ok(ValidateCallbackResult::Invalid("Only players can send messages".into()));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
+fn chat_base(session_header_hash: HeaderHashB64, round_header_hash: Option<HeaderHashB64>) -> ExternResult<EntryHash> {
+    let header_hash = round_header_hash.unwrap_or(session_header_hash);
+    let element = match get(HeaderHash::from(header_hash), GetOptions::content())? {
+        Some(element) => element,
+        None => return Err(WasmError::Guest("Session or round not found".into())),
+    };
+    entry_hash_from_element(element)
+}
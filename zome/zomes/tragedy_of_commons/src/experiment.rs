@@ -0,0 +1,270 @@
+use crate::{
+    game_round::GameRound,
+    game_session::{new_session, GameParams, GameSession, GameSessionInput, SessionParams},
+    random::SeededRng,
+    utils::{try_from_element, try_get_and_convert, try_get_by_header_and_convert},
+};
+use hdk::prelude::*;
+use holo_hash::*;
+
+/// A named variant of the game that an experiment compares with the others
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Treatment {
+    pub name: String,
+    pub game_params: GameParams,
+}
+
+/// An experiment runs the participants in sessions of session_size players, and every
+/// session plays one of the treatments. Who plays with whom, and which treatment, is drawn
+/// from the experiment entry hash, so anybody can check the assignment.
+#[hdk_entry(id = "experiment", visibility = "public")]
+#[derive(Clone)]
+pub struct Experiment {
+    pub owner: AgentPubKeyB64,
+    pub name: String,
+    pub treatments: Vec<Treatment>,
+    pub participants: Vec<AgentPubKeyB64>,
+    pub session_size: u32,
+}
+
+/// Record of a session that was created for an experiment
+#[hdk_entry(id = "experiment_assignment", visibility = "public")]
+#[derive(Clone)]
+pub struct ExperimentAssignment {
+    pub experiment: EntryHashB64,
+    /// index of the session in the assignment, see assign_participants
+    pub table: u32,
+    /// index into the treatments of the experiment
+    pub treatment: u32,
+    pub players: Vec<AgentPubKeyB64>,
+    pub session_header_hash: HeaderHashB64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct ExperimentInput {
+    pub name: String,
+    pub treatments: Vec<Treatment>,
+    pub participants: Vec<AgentPubKeyB64>,
+    pub session_size: u32,
+}
+
+#[hdk_extern]
+pub fn create_experiment(input: ExperimentInput) -> ExternResult<EntryHashB64> {
+    let experiment = Experiment {
+        owner: AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey),
+        name: input.name,
+        treatments: input.treatments,
+        participants: input.participants,
+        session_size: input.session_size,
+    };
+    create_entry(&experiment)?;
+    Ok(hash_entry(&experiment)?.into())
+}
+
+/// Creates all sessions of an experiment and records who was assigned to which session
+/// and treatment. Only the owner can run an experiment, and only once.
+/// Returns the header hashes of the first rounds of the new sessions.
+#[hdk_extern]
+pub fn run_experiment(experiment_hash: EntryHashB64) -> ExternResult<Vec<HeaderHashB64>> {
+    let experiment_entry_hash: EntryHash = experiment_hash.clone().into();
+    let experiment: Experiment = try_get_and_convert(experiment_entry_hash.clone())?;
+    if experiment.owner != AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey) {
+        return Err(WasmError::Guest("Only the owner of an experiment can run it".into()));
+    }
+    if !get_experiment_assignments(experiment_hash.clone())?.is_empty() {
+        return Err(WasmError::Guest("This experiment already ran".into()));
+    }
+
+    let mut round_header_hashes: Vec<HeaderHashB64> = vec![];
+    for (table, (treatment, players)) in assign_participants(&experiment, &experiment_entry_hash).into_iter().enumerate() {
+        let round_header_hash = new_session(GameSessionInput {
+            game_params: SessionParams::Custom(experiment.treatments[treatment].game_params.clone()),
+            players: players.clone(),
+            bots: vec![],
+            spectators: vec![],
+            public: false,
+        })?;
+        let round_one: GameRound = try_get_by_header_and_convert(round_header_hash.clone())?;
+
+        let assignment = ExperimentAssignment {
+            experiment: experiment_hash.clone(),
+            table: table as u32,
+            treatment: treatment as u32,
+            players,
+            session_header_hash: round_one.session_header_hash,
+        };
+        create_entry(&assignment)?;
+        create_link(
+            experiment_entry_hash.clone(),
+            hash_entry(&assignment)?,
+            LinkTag::new("experiment_assignment"),
+        )?;
+        round_header_hashes.push(round_header_hash.into());
+    }
+    Ok(round_header_hashes)
+}
+
+#[hdk_extern]
+pub fn get_experiment_assignments(experiment_hash: EntryHashB64) -> ExternResult<Vec<ExperimentAssignment>> {
+    let links = get_links(experiment_hash.into(), Some(LinkTag::new("experiment_assignment")))?;
+    let mut assignments: Vec<ExperimentAssignment> = vec![];
+    for link in links.into_inner() {
+        assignments.push(try_get_and_convert(link.target)?);
+    }
+    assignments.sort_by_key(|a| a.table);
+    Ok(assignments)
+}
+
+/// Shuffles the participants with a generator seeded by the experiment entry hash, seats them
+/// in sessions of session_size players and gives the sessions the treatments in turn, so every
+/// treatment gets about the same number of sessions. Participants that don't fill a complete
+/// session are left out. Returns the treatment index and the players of every session.
+pub fn assign_participants(experiment: &Experiment, experiment_entry_hash: &EntryHash) -> Vec<(usize, Vec<AgentPubKeyB64>)> {
+    let session_size = experiment.session_size as usize;
+    if session_size == 0 || experiment.treatments.is_empty() {
+        return vec![];
+    }
+    let mut rng = SeededRng::from_bytes(&[experiment_entry_hash.get_raw_32()]);
+    let mut participants = experiment.participants.clone();
+    for i in (1..participants.len()).rev() {
+        let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+        participants.swap(i, j);
+    }
+    participants
+        .chunks_exact(session_size)
+        .enumerate()
+        .map(|(table, players)| (table % experiment.treatments.len(), players.to_vec()))
+        .collect()
+}
+
+/// An experiment needs at least one valid treatment and enough distinct participants
+/// for one session
+#[hdk_extern]
+pub fn validate_create_entry_experiment(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let experiment: Experiment = try_from_element(data.element.clone())?;
+    if data.element.header().author() != &AgentPubKey::from(experiment.owner.clone()) {
+        return Ok(ValidateCallbackResult::Invalid("An experiment can only be created by its owner".into()));
+    }
+    if experiment.treatments.is_empty() {
+        return Ok(ValidateCallbackResult::Invalid("An experiment needs at least one treatment".into()));
+    }
+    for treatment in experiment.treatments.iter() {
+        if treatment.name.trim().is_empty() {
+            return Ok(ValidateCallbackResult::Invalid("A treatment needs a name".into()));
+        }
+        if let Err(reason) = treatment.game_params.validate() {
+            return Ok(ValidateCallbackResult::Invalid(reason));
+        }
+    }
+    if experiment.session_size < 2 || experiment.participants.len() < experiment.session_size as usize {
+        return Ok(ValidateCallbackResult::Invalid("An experiment needs at least one session of two or more players".into()));
+    }
+    for (i, participant) in experiment.participants.iter().enumerate() {
+        if experiment.participants[..i].contains(participant) {
+            return Ok(ValidateCallbackResult::Invalid("A participant can only join an experiment once".into()));
+        }
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
+/// An assignment has to be made by the owner of the experiment, match the drawn assignment
+/// and point at a session with those players and the params of the treatment
+#[hdk_extern]
+pub fn validate_create_entry_experiment_assignment(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let assignment: ExperimentAssignment = try_from_element(data.element.clone())?;
+    let experiment_entry_hash: EntryHash = assignment.experiment.clone().into();
+    let experiment: Experiment = match get(experiment_entry_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![experiment_entry_hash.into()])),
+    };
+    if data.element.header().author() != &AgentPubKey::from(experiment.owner.clone()) {
+        return Ok(ValidateCallbackResult::Invalid("Only the owner of an experiment can assign participants".into()));
+    }
+    let drawn = assign_participants(&experiment, &experiment_entry_hash);
+    match drawn.get(assignment.table as usize) {
+        Some((treatment, players)) if *treatment as u32 == assignment.treatment && players == &assignment.players => {}
+        _ => return Ok(ValidateCallbackResult::Invalid("Assignment doesn't match the drawn assignment".into())),
+    }
+
+    let session_header_hash: HeaderHash = assignment.session_header_hash.clone().into();
+    let game_session: GameSession = match get(session_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![session_header_hash.into()])),
+    };
+    if game_session.players != assignment.players
+        || game_session.game_params != experiment.treatments[assignment.treatment as usize].game_params
+    {
+        return Ok(ValidateCallbackResult::Invalid("Session doesn't match the assignment".into()));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed_point::Fixed;
+    use crate::regeneration::RegenerationModel;
+
+    fn treatment(name: &str) -> Treatment {
+        Treatment {
+            name: name.into(),
+            game_params: GameParams::single_pool("fish", Fixed::from_int(100), RegenerationModel::Exponential { rate: Fixed::ZERO }),
+        }
+    }
+
+    fn experiment(participants: u8, session_size: u32) -> Experiment {
+        Experiment {
+            owner: AgentPubKey::from_raw_36(vec![0; 36]).into(),
+            name: "test".into(),
+            treatments: vec![treatment("control"), treatment("sanctions")],
+            participants: (1..=participants).map(|seat| AgentPubKey::from_raw_36(vec![seat; 36]).into()).collect(),
+            session_size,
+        }
+    }
+
+    fn entry_hash(byte: u8) -> EntryHash {
+        EntryHash::from_raw_36(vec![byte; 36])
+    }
+
+    #[test]
+    fn same_entry_hash_gives_same_assignment() {
+        let experiment = experiment(9, 3);
+        assert_eq!(
+            assign_participants(&experiment, &entry_hash(1)),
+            assign_participants(&experiment, &entry_hash(1))
+        );
+        assert_ne!(
+            assign_participants(&experiment, &entry_hash(1)),
+            assign_participants(&experiment, &entry_hash(2))
+        );
+    }
+
+    #[test]
+    fn treatments_are_given_in_turn() {
+        let assignment = assign_participants(&experiment(12, 3), &entry_hash(1));
+        let treatments: Vec<usize> = assignment.iter().map(|(treatment, _)| *treatment).collect();
+        assert_eq!(treatments, vec![0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn participants_are_seated_once_and_leftovers_sit_out() {
+        let experiment = experiment(8, 3);
+        let seated: Vec<AgentPubKeyB64> = assign_participants(&experiment, &entry_hash(1))
+            .into_iter()
+            .flat_map(|(_, players)| players)
+            .collect();
+        assert_eq!(seated.len(), 6);
+        for (i, player) in seated.iter().enumerate() {
+            assert!(experiment.participants.contains(player));
+            assert!(!seated[..i].contains(player));
+        }
+    }
+
+    #[test]
+    fn nothing_to_assign_without_treatments_or_seats() {
+        let mut without_treatments = experiment(6, 3);
+        without_treatments.treatments = vec![];
+        assert!(assign_participants(&without_treatments, &entry_hash(1)).is_empty());
+        assert!(assign_participants(&experiment(6, 0), &entry_hash(1)).is_empty());
+    }
+}
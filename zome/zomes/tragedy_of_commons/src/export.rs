@@ -0,0 +1,236 @@
+use crate::{
+    game_move::GameMove,
+    game_round::GameRound,
+    game_session::{GameParams, GameScores, GameSession},
+    types::{PlayerStats, ResourcePools},
+    utils::{entry_hash_from_element, try_from_element, try_get_and_convert, try_get_latest_element},
+};
+use crate::properties::am_i_researcher;
+use chrono::NaiveDateTime;
+use hdk::prelude::*;
+use holo_hash::*;
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct ExportInput {
+    pub session_header_hash: HeaderHashB64,
+    /// when given, every agent key and hash is replaced by a pseudonym derived from this salt,
+    /// see pseudonymise. The salt is never stored, so the mapping only exists for
+    /// whoever knows the salt.
+    pub salt: Option<String>,
+}
+
+/// Everything that happened in a session, for research analysis
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct SessionExport {
+    pub session_header_hash: HeaderHashB64,
+    pub session: GameSession,
+    pub game_params: GameParams,
+    /// oldest round first
+    pub rounds: Vec<RoundExport>,
+    /// None while the game isn't over
+    pub scores: Option<GameScores>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoundExport {
+    pub round_header_hash: HeaderHashB64,
+    /// latest version of the round
+    pub round: GameRound,
+    pub moves: Vec<MoveExport>,
+}
+
+/// Version of a move the round was calculated from, see export_moves
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MoveExport {
+    pub move_header_hash: HeaderHashB64,
+    /// player the move was made for, which isn't the header author for bot seats
+    pub author: AgentPubKeyB64,
+    pub timestamp: Timestamp,
+    pub resources: ResourcePools,
+}
+
+#[hdk_extern]
+pub fn export_session(input: ExportInput) -> ExternResult<SessionExport> {
+    let export = build_export(input.session_header_hash)?;
+    match input.salt {
+        Some(salt) if salt.is_empty() => Err(WasmError::Guest("Salt for pseudonyms can't be empty".into())),
+        Some(salt) => Ok(pseudonymise(export, &salt)),
+        None => Ok(export),
+    }
+}
+
+fn build_export(session_header_hash: HeaderHashB64) -> ExternResult<SessionExport> {
+    let element = match get(HeaderHash::from(session_header_hash.clone()), GetOptions::content())? {
+        Some(element) => element,
+        None => return Err(WasmError::Guest("Session not found".into())),
+    };
+    let session_entry_hash = entry_hash_from_element(element.clone())?;
+    let session: GameSession = try_from_element(element)?;
+    // players can export their own sessions, the researcher can export any session
+    let me = AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey);
+    if session.owner != me && !session.players.contains(&me) && !am_i_researcher()? {
+        return Err(WasmError::Guest("Only players and the researcher can export a session".into()));
+    }
+
+    let mut rounds: Vec<RoundExport> = vec![];
+    for link in get_links(session_entry_hash.clone(), Some(LinkTag::new("game_round")))?.into_inner() {
+        let round_element = match get(link.target.clone(), GetOptions::content())? {
+            Some(element) => element,
+            None => continue,
+        };
+        let round: GameRound = try_from_element(try_get_latest_element(link.target.clone())?)?;
+        // anybody can link a round from the session, only export the rounds of this session
+        if round.session_header_hash != session_header_hash {
+            continue;
+        }
+        rounds.push(RoundExport {
+            round_header_hash: round_element.header_address().clone().into(),
+            moves: export_moves(link.target, &round)?,
+            round,
+        });
+    }
+    rounds.sort_by_key(|r| r.round.round_num);
+
+    let scores: Option<GameScores> = match get_links(session_entry_hash, Some(LinkTag::new("game_scores")))?
+        .into_inner()
+        .into_iter()
+        .next()
+    {
+        Some(link) => Some(try_get_and_convert(link.target)?),
+        None => None,
+    };
+
+    Ok(SessionExport {
+        session_header_hash,
+        game_params: session.game_params.clone(),
+        session,
+        rounds,
+        scores,
+    })
+}
+
+/// Flat rendering of the moves of a session, one line per round, player and resource pool
+#[hdk_extern]
+pub fn export_session_csv(input: ExportInput) -> ExternResult<String> {
+    Ok(moves_csv(&export_session(input)?))
+}
+
+pub fn moves_csv(export: &SessionExport) -> String {
+    let mut csv = String::from("round,player,resource,amount,timestamp\n");
+    for round in export.rounds.iter() {
+        for game_move in round.moves.iter() {
+            for (resource, amount) in game_move.resources.iter() {
+                csv.push_str(&format!(
+                    "{},{},{},{},{}\n",
+                    round.round.round_num,
+                    AgentPubKey::from(game_move.author.clone()),
+                    resource,
+                    amount,
+                    NaiveDateTime::from_timestamp(game_move.timestamp.0, game_move.timestamp.1).format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+                ));
+            }
+        }
+    }
+    csv
+}
+
+// NOTE: hashes would let anybody with access to the DHT look up who wrote a session, round
+// or move, so they are pseudonymised too. The same hash always gets the same pseudonym,
+// so rows can still be matched with each other.
+/// Replaces every agent key and every hash in the export with a pseudonym that is the same
+/// for a key or hash within one session, and different across sessions and salts.
+pub fn pseudonymise(mut export: SessionExport, salt: &str) -> SessionExport {
+    let session_header_hash = export.session_header_hash.clone();
+    let p = |agent: &AgentPubKeyB64| -> AgentPubKeyB64 {
+        pseudonym(salt, &session_header_hash, &AgentPubKey::from(agent.clone())).into()
+    };
+    let header = |hash: &HeaderHashB64| -> HeaderHashB64 {
+        pseudonym(salt, &session_header_hash, &HeaderHash::from(hash.clone())).into()
+    };
+    let entry = |hash: &EntryHash| -> EntryHash { pseudonym(salt, &session_header_hash, hash) };
+    let entry_b64 = |hash: &EntryHashB64| -> EntryHashB64 { entry(&EntryHash::from(hash.clone())).into() };
+
+    export.session_header_hash = header(&export.session_header_hash);
+    let session = &mut export.session;
+    session.owner = p(&session.owner);
+    session.players = session.players.iter().map(p).collect();
+    session.spectators = session.spectators.iter().map(p).collect();
+    for bot in session.bots.iter_mut() {
+        bot.agent = p(&bot.agent);
+    }
+    session.preset = session.preset.as_ref().map(entry_b64);
+    session.previous_session = session.previous_session.as_ref().map(header);
+    if let Some(table) = session.tournament.as_mut() {
+        table.tournament = entry_b64(&table.tournament);
+    }
+    for round in export.rounds.iter_mut() {
+        round.round_header_hash = header(&round.round_header_hash);
+        round.round.session_header_hash = header(&round.round.session_header_hash);
+        round.round.player_stats = pseudonymise_stats(&round.round.player_stats, &p);
+        round.round.player_moves = round.round.player_moves.iter().map(entry).collect();
+        round.round.player_sanctions = round.round.player_sanctions.iter().map(entry).collect();
+        round.round.player_votes = round.round.player_votes.iter().map(entry).collect();
+        for game_move in round.moves.iter_mut() {
+            game_move.move_header_hash = header(&game_move.move_header_hash);
+            game_move.author = p(&game_move.author);
+        }
+    }
+    if let Some(scores) = export.scores.as_mut() {
+        scores.session = entry_b64(&scores.session);
+        scores.last_round = header(&scores.last_round);
+        scores.stats = pseudonymise_stats(&scores.stats, &p);
+    }
+    export
+}
+
+fn pseudonymise_stats(stats: &PlayerStats, p: &dyn Fn(&AgentPubKeyB64) -> AgentPubKeyB64) -> PlayerStats {
+    stats.iter().map(|(agent, stat)| (p(agent), stat.clone())).collect()
+}
+
+/// A made up agent key or hash of the same type, hashed from the salt, the session
+/// and the real one
+fn pseudonym<T: hash_type::PrimitiveHashType>(salt: &str, session_header_hash: &HeaderHashB64, real: &HoloHash<T>) -> HoloHash<T> {
+    let hash = blake2b_simd::Params::new()
+        .hash_length(32)
+        .to_state()
+        .update(&(salt.len() as u64).to_le_bytes())
+        .update(salt.as_bytes())
+        .update(HeaderHash::from(session_header_hash.clone()).get_raw_39())
+        .update(real.get_raw_39())
+        .finalize();
+    let mut bytes = hash.as_bytes().to_vec();
+    bytes.extend_from_slice(&[0; 4]);
+    HoloHash::<T>::from_raw_36(bytes)
+}
+
+/// Moves of a closed round are the versions it lists, updates written after it closed don't
+/// count. For a round that is still open they are the latest versions of the linked moves.
+fn export_moves(round_entry_hash: EntryHash, round: &GameRound) -> ExternResult<Vec<MoveExport>> {
+    let mut elements: Vec<Element> = vec![];
+    if round.player_moves.is_empty() {
+        for link in get_links(round_entry_hash, Some(LinkTag::new("game_move")))?.into_inner() {
+            elements.push(try_get_latest_element(link.target)?);
+        }
+    } else {
+        for move_entry_hash in round.player_moves.iter() {
+            match get(move_entry_hash.clone(), GetOptions::content())? {
+                Some(element) => elements.push(element),
+                None => return Err(WasmError::Guest("Move of a closed round not found".into())),
+            }
+        }
+    }
+    let mut moves: Vec<MoveExport> = vec![];
+    for element in elements {
+        let header_hash = element.header_address().clone();
+        let timestamp = element.header().timestamp();
+        let game_move: GameMove = try_from_element(element)?;
+        moves.push(MoveExport {
+            move_header_hash: header_hash.into(),
+            author: game_move.owner.into(),
+            timestamp,
+            resources: game_move.resources,
+        });
+    }
+    moves.sort_by_key(|m| m.timestamp);
+    Ok(moves)
+}
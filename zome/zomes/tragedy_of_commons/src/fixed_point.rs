@@ -0,0 +1,211 @@
+use hdk::prelude::*;
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign},
+};
+
+/// Number of decimal places every Fixed value carries
+pub const FIXED_DECIMALS: u32 = 3;
+/// Raw value of Fixed::ONE, i.e. 10^FIXED_DECIMALS
+pub const FIXED_SCALE: i64 = 1_000;
+
+/// Deterministic fixed-point number with three decimal places.
+///
+/// Round calculation runs on every agent that closes or validates a round, so it has to give
+/// bit-identical results everywhere, which floats can't guarantee. A Fixed value is stored
+/// (and serialized) as a whole number of thousandths: `1.1` is `1100`, `100` is `100000`.
+///
+/// Rounding rules:
+/// - addition and subtraction are exact
+/// - multiplication and division round to the nearest thousandth, halves away from zero
+/// - results that don't fit are saturated to the min/max value instead of wrapping
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Fixed(i64);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(FIXED_SCALE);
+
+    /// Creates a value from a whole number, e.g. from_int(3) == 3.000
+    pub const fn from_int(value: i64) -> Fixed {
+        Fixed(value.saturating_mul(FIXED_SCALE))
+    }
+
+    /// Creates a value from a number of thousandths, e.g. from_raw(1100) == 1.100
+    pub const fn from_raw(raw: i64) -> Fixed {
+        Fixed(raw)
+    }
+
+    /// Number of thousandths this value holds
+    pub const fn raw(self) -> i64 {
+        self.0
+    }
+
+    /// Whole part of the value, rounded towards negative infinity
+    pub fn floor_int(self) -> i64 {
+        self.0.div_euclid(FIXED_SCALE)
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+
+    /// Quotient rounded to the nearest thousandth, halves away from zero.
+    /// Returns None when dividing by zero.
+    pub fn checked_div(self, rhs: Fixed) -> Option<Fixed> {
+        if rhs.0 == 0 {
+            return None;
+        }
+        Some(Fixed::saturate(div_round(self.0 as i128 * FIXED_SCALE as i128, rhs.0 as i128)))
+    }
+
+    /// Multiplies by a whole number, this is exact unless it saturates
+    pub fn mul_int(self, rhs: i64) -> Fixed {
+        Fixed(self.0.saturating_mul(rhs))
+    }
+
+    fn saturate(value: i128) -> Fixed {
+        if value > i64::MAX as i128 {
+            Fixed(i64::MAX)
+        } else if value < i64::MIN as i128 {
+            Fixed(i64::MIN)
+        } else {
+            Fixed(value as i64)
+        }
+    }
+}
+
+/// Integer division rounding to the nearest integer, halves away from zero
+fn div_round(numerator: i128, denominator: i128) -> i128 {
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    if 2 * remainder.abs() >= denominator.abs() {
+        if (numerator < 0) != (denominator < 0) {
+            quotient - 1
+        } else {
+            quotient + 1
+        }
+    } else {
+        quotient
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_sub(rhs.0))
+    }
+}
+
+/// Product rounded to the nearest thousandth, halves away from zero
+impl Mul for Fixed {
+    type Output = Fixed;
+    fn mul(self, rhs: Fixed) -> Fixed {
+        Fixed::saturate(div_round(self.0 as i128 * rhs.0 as i128, FIXED_SCALE as i128))
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+    fn neg(self) -> Fixed {
+        Fixed(self.0.saturating_neg())
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, rhs: Fixed) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, rhs: Fixed) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sum for Fixed {
+    fn sum<I: Iterator<Item = Fixed>>(iter: I) -> Fixed {
+        iter.fold(Fixed::ZERO, |acc, x| acc + x)
+    }
+}
+
+impl<'a> Sum<&'a Fixed> for Fixed {
+    fn sum<I: Iterator<Item = &'a Fixed>>(iter: I) -> Fixed {
+        iter.fold(Fixed::ZERO, |acc, x| acc + *x)
+    }
+}
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = (self.0 as i128).abs();
+        let scale = FIXED_SCALE as i128;
+        write!(f, "{}{}.{:03}", sign, abs / scale, abs % scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiplication_rounds_halves_away_from_zero() {
+        // 1.5 thousandths rounds to 2, -1.5 to -2
+        assert_eq!(Fixed::from_raw(1500) * Fixed::from_raw(1), Fixed::from_raw(2));
+        assert_eq!(Fixed::from_raw(-1500) * Fixed::from_raw(1), Fixed::from_raw(-2));
+        assert_eq!(Fixed::from_raw(1499) * Fixed::from_raw(1), Fixed::from_raw(1));
+        assert_eq!(Fixed::from_raw(-1499) * Fixed::from_raw(1), Fixed::from_raw(-1));
+        assert_eq!(Fixed::from_raw(1100) * Fixed::from_int(100), Fixed::from_int(110));
+    }
+
+    #[test]
+    fn division_rounds_halves_away_from_zero() {
+        assert_eq!(Fixed::ONE.checked_div(Fixed::from_int(3)), Some(Fixed::from_raw(333)));
+        assert_eq!(Fixed::from_int(2).checked_div(Fixed::from_int(3)), Some(Fixed::from_raw(667)));
+        assert_eq!(Fixed::from_int(-2).checked_div(Fixed::from_int(3)), Some(Fixed::from_raw(-667)));
+        assert_eq!(Fixed::from_raw(1).checked_div(Fixed::from_int(2)), Some(Fixed::from_raw(1)));
+        assert_eq!(Fixed::from_raw(-1).checked_div(Fixed::from_int(2)), Some(Fixed::from_raw(-1)));
+        assert_eq!(Fixed::ONE.checked_div(Fixed::ZERO), None);
+    }
+
+    #[test]
+    fn results_saturate_instead_of_wrapping() {
+        let max = Fixed::from_raw(i64::MAX);
+        let min = Fixed::from_raw(i64::MIN);
+        assert_eq!(max + Fixed::ONE, max);
+        assert_eq!(min - Fixed::ONE, min);
+        assert_eq!(max * Fixed::from_int(2), max);
+        assert_eq!(max * Fixed::from_int(-2), min);
+        assert_eq!(max.mul_int(2), max);
+        assert_eq!(Fixed::from_int(i64::MAX), max);
+        assert_eq!(-min, max);
+        assert_eq!(max.checked_div(Fixed::from_raw(1)), Some(max));
+    }
+
+    #[test]
+    fn floor_int_rounds_towards_negative_infinity() {
+        assert_eq!(Fixed::from_raw(1999).floor_int(), 1);
+        assert_eq!(Fixed::from_raw(-1).floor_int(), -1);
+        assert_eq!(Fixed::from_int(-2).floor_int(), -2);
+    }
+
+    #[test]
+    fn display_shows_three_decimals() {
+        assert_eq!(Fixed::from_raw(1500).to_string(), "1.500");
+        assert_eq!(Fixed::from_raw(-1500).to_string(), "-1.500");
+        assert_eq!(Fixed::from_raw(5).to_string(), "0.005");
+        assert_eq!(Fixed::from_raw(-5).to_string(), "-0.005");
+        assert_eq!(Fixed::ZERO.to_string(), "0.000");
+        assert_eq!(Fixed::from_raw(i64::MIN).to_string(), "-9223372036854775.808");
+    }
+}
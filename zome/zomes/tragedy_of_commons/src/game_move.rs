@@ -2,11 +2,14 @@ use std::{collections::HashMap, vec};
 
 use crate::prelude::SignedHeader;
 use crate::utils::entry_hash_from_element;
+use crate::session_control::ensure_session_running;
 use crate::{
+    bots::{bot_plan, bot_seat, may_play_for},
     game_round::{self, calculate_round_state, GameRound, RoundState},
     game_session::{GameSession, GameSignal, SessionState},
-    types::ResourceAmount,
-    utils::{convert_keys_from_b64, try_get_and_convert, try_get_game_moves},
+    fixed_point::Fixed,
+    types::{ResourceAmount, ResourcePools},
+    utils::{convert_keys_from_b64, try_from_element, try_get_and_convert, try_get_game_moves, try_get_latest_element},
 };
 use hdk::prelude::*;
 use holo_hash::*;
@@ -20,11 +23,22 @@ pub struct GameMove {
     // a move, maybe we could pass that value from there, so that every player has it
     // when they're making a move
     pub round: EntryHash,
-    pub resources: ResourceAmount,
+    /// amount taken from every resource pool, pools that aren't listed are left untouched
+    pub resources: ResourcePools,
+    /// version of the round that holds the quota the move has to stay within,
+    /// only set in sessions with quota voting
+    pub quota_header_hash: Option<HeaderHash>,
+}
+
+impl GameMove {
+    /// Amount this move takes from the resource pool with the given name
+    pub fn amount_of(&self, resource_name: &str) -> ResourceAmount {
+        self.resources.get(resource_name).copied().unwrap_or(Fixed::ZERO)
+    }
 }
 #[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
 pub struct GameMoveInput {
-    pub resource_amount: ResourceAmount,
+    pub resources: ResourcePools,
     // NOTE: if we're linking all moves to the round, this can never be None
     // as we'll need a base for the link. Instead moves for the round 0 could be
     // linked directly from the game session.
@@ -51,19 +65,31 @@ for the context, here are notes on how we've made this decision:
 */
 #[hdk_extern]
 pub fn new_move(input: GameMoveInput) -> ExternResult<HeaderHash> {
+    new_move_for(agent_info()?.agent_initial_pubkey, input)
+}
 
+/// Makes the move of the given seat, which is the caller itself unless it plays a bot seat
+pub fn new_move_for(owner: AgentPubKey, input: GameMoveInput) -> ExternResult<HeaderHash> {
     let game_round_header_hash:HeaderHash = input.current_round_header_hash.into();
     let game_round_element = match get(game_round_header_hash, GetOptions::content())? {
         Some(element) => element,
         None => return Err(WasmError::Guest("Current round not found".into())),
     };
     let game_round_entry_hash = entry_hash_from_element(game_round_element)?;
+
+    let latest_round_element = try_get_latest_element(game_round_entry_hash.clone())?;
+    let latest_round: GameRound = try_from_element(latest_round_element.clone())?;
+    if latest_round.round_state == RoundState::Voting {
+        return Err(WasmError::Guest("Cannot make a move: wait until the quota is decided".into()));
+    }
+    ensure_session_running(latest_round.session_header_hash.clone())?;
    
     // todo: add guard clauses for empty input
     let game_move = GameMove {
-        owner: agent_info()?.agent_initial_pubkey,
-        resources: input.resource_amount,
+        owner,
+        resources: input.resources,
         round: game_round_entry_hash.clone(),
+        quota_header_hash: latest_round.quota.as_ref().map(|_| latest_round_element.header_address().clone()),
     };
     let header_hash_game_move = create_entry(&game_move)?;
     let entry_hash_game_move = hash_entry(&game_move)?;
@@ -80,6 +106,140 @@ pub fn new_move(input: GameMoveInput) -> ExternResult<HeaderHash> {
     Ok(game_move_link.into())
 }
 
+/// Lets a player change the move they already made for the current round, as long as
+/// the round isn't finished yet. Input is the same as for new_move: the round header hash
+/// is used to find the caller's move among the moves linked from the round.
+#[hdk_extern]
+pub fn update_move(input: GameMoveInput) -> ExternResult<HeaderHash> {
+    let game_round_header_hash: HeaderHash = input.current_round_header_hash.into();
+    let game_round_element = match get(game_round_header_hash, GetOptions::content())? {
+        Some(element) => element,
+        None => return Err(WasmError::Guest("Current round not found".into())),
+    };
+    let game_round_entry_hash = entry_hash_from_element(game_round_element)?;
+
+    // the round might have been closed in the meantime, so check its latest version
+    let latest_round_element = try_get_latest_element(game_round_entry_hash.clone())?;
+    let latest_round: GameRound = try_from_element(latest_round_element.clone())?;
+    if latest_round.round_state != RoundState::InProgress {
+        return Err(WasmError::Guest("Cannot update move: all moves of this round are already made".into()));
+    }
+    ensure_session_running(latest_round.session_header_hash.clone())?;
+
+    let owner = agent_info()?.agent_initial_pubkey;
+    let links = get_links(game_round_entry_hash.clone(), Some(LinkTag::new("game_move")))?;
+    for link in links.into_inner() {
+        let latest_move_element = try_get_latest_element(link.target)?;
+        let latest_move: GameMove = try_from_element(latest_move_element.clone())?;
+        if latest_move.owner == owner {
+            let game_move = GameMove {
+                owner,
+                resources: input.resources,
+                round: game_round_entry_hash,
+                quota_header_hash: latest_round.quota.as_ref().map(|_| latest_round_element.header_address().clone()),
+            };
+            return update_entry(latest_move_element.header_address().clone(), &game_move);
+        }
+    }
+    Err(WasmError::Guest("Cannot update move: no move found for this round".into()))
+}
+
+#[hdk_extern]
+pub fn validate_create_entry_game_move(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let game_move: GameMove = try_from_element(data.element.clone())?;
+    validate_move_in_session(&game_move, data.element.header().author())
+}
+
+/// An updated move has to keep its owner and round, and can only be written by its owner.
+/// Whether it counts is up to the round: a closed round lists the versions of the moves
+/// it was calculated from, so an update written after the round closed is simply ignored.
+#[hdk_extern]
+pub fn validate_update_entry_game_move(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let game_move: GameMove = try_from_element(data.element.clone())?;
+    let original_header_hash = match data.element.header() {
+        Header::Update(update) => update.original_header_address.clone(),
+        _ => return Ok(ValidateCallbackResult::Invalid("Expected an update header".into())),
+    };
+    let original_move: GameMove = match get(original_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![original_header_hash.into()])),
+    };
+
+    if data.element.header().author() != &game_move.owner {
+        return Ok(ValidateCallbackResult::Invalid("Only the owner of a move can update it".into()));
+    }
+    if game_move.owner != original_move.owner || game_move.round != original_move.round {
+        return Ok(ValidateCallbackResult::Invalid("An updated move must keep its owner and round".into()));
+    }
+    validate_move_in_session(&game_move, data.element.header().author())
+}
+
+/// Only players can make moves, spectators of a session can't. A move is made by its owner,
+/// or for a bot seat by an agent that plays the bots of the round, see bots::may_play_for.
+/// A move can only take non negative amounts from the resource pools of its session.
+/// In sessions with quota voting a move can't take more than the quota of its round
+/// from any resource pool. The quota is read from the round version the move points to.
+/// A bot seat has to take exactly what its strategy plans for the round.
+fn validate_move_in_session(game_move: &GameMove, author: &AgentPubKey) -> ExternResult<ValidateCallbackResult> {
+    let game_round: GameRound = match get(game_move.round.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![game_move.round.clone().into()])),
+    };
+    let session_header_hash: HeaderHash = game_round.session_header_hash.clone().into();
+    let game_session: GameSession = match get(session_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![session_header_hash.into()])),
+    };
+    if !game_session.players.contains(&AgentPubKeyB64::from(game_move.owner.clone())) {
+        return Ok(ValidateCallbackResult::Invalid("Only players can make moves".into()));
+    }
+    if !may_play_for(&game_session, game_round.round_num, &game_move.owner, author) {
+        return Ok(ValidateCallbackResult::Invalid("A move can only be made by its owner".into()));
+    }
+    let resource_names = game_session.game_params.resource_names();
+    for (name, amount) in game_move.resources.iter() {
+        if !resource_names.contains(name) {
+            return Ok(ValidateCallbackResult::Invalid(format!("Session has no resource pool named {}", name)));
+        }
+        if *amount < Fixed::ZERO {
+            return Ok(ValidateCallbackResult::Invalid("A move can't take a negative amount".into()));
+        }
+    }
+    let mut quota = None;
+    if game_session.game_params.quota_voting.is_some() {
+        let quota_header_hash = match game_move.quota_header_hash.clone() {
+            Some(hash) => hash,
+            None => return Ok(ValidateCallbackResult::Invalid("Move has to refer to the quota of its round".into())),
+        };
+        let quota_element = match get(quota_header_hash.clone(), GetOptions::content())? {
+            Some(element) => element,
+            None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![quota_header_hash.into()])),
+        };
+        match quota_element.header() {
+            Header::Update(update) if update.original_entry_address == game_move.round => {}
+            _ => return Ok(ValidateCallbackResult::Invalid("Move refers to the quota of another round".into())),
+        }
+        let quota_round: GameRound = try_from_element(quota_element)?;
+        let round_quota = match quota_round.quota {
+            Some(quota) => quota,
+            None => return Ok(ValidateCallbackResult::Invalid("Move refers to a round without quota".into())),
+        };
+        for (name, cap) in round_quota.iter() {
+            if game_move.amount_of(name) > *cap {
+                return Ok(ValidateCallbackResult::Invalid("Move takes more than the quota allows".into()));
+            }
+        }
+        quota = Some(round_quota);
+    }
+    // bots draw their numbers from the round, so what they take can be recomputed
+    if let Some(seat) = bot_seat(&game_session, &game_move.owner) {
+        if game_move.resources != bot_plan(&game_session, seat, &game_round, &game_move.round, quota.as_ref()) {
+            return Ok(ValidateCallbackResult::Invalid("A bot seat has to take what its strategy plans".into()));
+        }
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
 // Question: how do we make moves discoverable by the players?
 // Option1: make a link from game session / game round to which this move belongs?
 //      note: this is where things start to get more complicated with the game round that is
@@ -0,0 +1,144 @@
+use crate::{
+    game_session::GameParams,
+    properties::{am_i_researcher, is_researcher, DnaProperties},
+    utils::{try_from_element, try_get_latest_element},
+};
+use hdk::prelude::*;
+use holo_hash::*;
+
+const PRESETS_PATH: &str = "presets";
+
+/// Named GameParams that can be reused to start sessions, e.g. "classic" or "harsh winter".
+/// Presets can be edited by their owner, sessions started from a preset keep their own copy
+/// of the params.
+#[hdk_entry(id = "game_preset", visibility = "public")]
+#[derive(Clone)]
+pub struct GamePreset {
+    pub owner: AgentPubKeyB64,
+    pub name: String,
+    pub description: String,
+    pub game_params: GameParams,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct GamePresetInput {
+    pub name: String,
+    pub description: String,
+    pub game_params: GameParams,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct UpdateGamePresetInput {
+    /// entry hash of the preset as it was created, which is what sessions refer to
+    pub preset_hash: EntryHashB64,
+    pub preset: GamePresetInput,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct GamePresetOutput {
+    pub preset_hash: EntryHashB64,
+    /// latest version of the preset
+    pub preset: GamePreset,
+}
+
+/// Creates a preset and links it from the "presets" path, returns the entry hash to start sessions with
+#[hdk_extern]
+pub fn create_preset(input: GamePresetInput) -> ExternResult<EntryHashB64> {
+    if DnaProperties::get()?.researcher_address.is_some() && !am_i_researcher()? {
+        return Err(WasmError::Guest("Only the researcher can publish presets".into()));
+    }
+    let preset = GamePreset {
+        owner: AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey),
+        name: input.name,
+        description: input.description,
+        game_params: input.game_params,
+    };
+    create_entry(&preset)?;
+    let preset_entry_hash = hash_entry(&preset)?;
+
+    let path = Path::from(PRESETS_PATH);
+    path.ensure()?;
+    create_link(path.hash()?, preset_entry_hash.clone(), LinkTag::new("game_preset"))?;
+    Ok(preset_entry_hash.into())
+}
+
+/// Edits a preset, sessions that were already started from it are not affected
+#[hdk_extern]
+pub fn update_preset(input: UpdateGamePresetInput) -> ExternResult<HeaderHashB64> {
+    let latest_element = try_get_latest_element(input.preset_hash.into())?;
+    let latest_preset: GamePreset = try_from_element(latest_element.clone())?;
+    let preset = GamePreset {
+        owner: latest_preset.owner,
+        name: input.preset.name,
+        description: input.preset.description,
+        game_params: input.preset.game_params,
+    };
+    let header_hash = update_entry(latest_element.header_address().clone(), &preset)?;
+    Ok(header_hash.into())
+}
+
+/// Lists the latest version of every preset
+#[hdk_extern]
+pub fn get_presets(_: ()) -> ExternResult<Vec<GamePresetOutput>> {
+    let path = Path::from(PRESETS_PATH);
+    let links = get_links(path.hash()?, Some(LinkTag::new("game_preset")))?;
+    let mut presets: Vec<GamePresetOutput> = vec![];
+    for link in links.into_inner() {
+        let preset: GamePreset = try_from_element(try_get_latest_element(link.target.clone())?)?;
+        presets.push(GamePresetOutput {
+            preset_hash: link.target.into(),
+            preset,
+        });
+    }
+    Ok(presets)
+}
+
+/// Fetches the latest version of a preset
+#[hdk_extern]
+pub fn get_preset(preset_hash: EntryHashB64) -> ExternResult<GamePreset> {
+    try_from_element(try_get_latest_element(preset_hash.into())?)
+}
+
+/// A preset needs a name and params a session can be started with.
+/// In deployments with a researcher only the researcher can publish presets.
+#[hdk_extern]
+pub fn validate_create_entry_game_preset(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let preset: GamePreset = try_from_element(data.element.clone())?;
+    if data.element.header().author() != &AgentPubKey::from(preset.owner.clone()) {
+        return Ok(ValidateCallbackResult::Invalid("A preset can only be created by its owner".into()));
+    }
+    if DnaProperties::get()?.researcher_address.is_some() && !is_researcher(data.element.header().author())? {
+        return Ok(ValidateCallbackResult::Invalid("Only the researcher can publish presets".into()));
+    }
+    validate_preset(&preset)
+}
+
+/// Only the owner can edit a preset, and the owner can't change
+#[hdk_extern]
+pub fn validate_update_entry_game_preset(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let preset: GamePreset = try_from_element(data.element.clone())?;
+    let original_header_hash = match data.element.header() {
+        Header::Update(update) => update.original_header_address.clone(),
+        _ => return Ok(ValidateCallbackResult::Invalid("Expected an update header".into())),
+    };
+    let original_preset: GamePreset = match get(original_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![original_header_hash.into()])),
+    };
+    if data.element.header().author() != &AgentPubKey::from(original_preset.owner.clone())
+        || preset.owner != original_preset.owner
+    {
+        return Ok(ValidateCallbackResult::Invalid("Only the owner of a preset can edit it".into()));
+    }
+    validate_preset(&preset)
+}
+
+fn validate_preset(preset: &GamePreset) -> ExternResult<ValidateCallbackResult> {
+    if preset.name.trim().is_empty() {
+        return Ok(ValidateCallbackResult::Invalid("A preset needs a name".into()));
+    }
+    if let Err(reason) = preset.game_params.validate() {
+        return Ok(ValidateCallbackResult::Invalid(reason));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
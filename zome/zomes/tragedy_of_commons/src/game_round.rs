@@ -1,27 +1,58 @@
 use crate::game_move::GameMove;
-use crate::game_session::{GameParams, GameScores, GameSession, GameSignal, SessionState, SignalPayloadGameOver, SignalPayloadNextRound};
-use crate::types::{PlayerStats, ReputationAmount, ResourceAmount};
-use crate::utils::{convert_keys_from_b64, entry_hash_from_element, try_from_element, try_get_and_convert, try_get_by_header_and_convert, try_get_game_moves};
+use crate::bots::play_bot_seats;
+use crate::regeneration::{shock_rng, Shock};
+use crate::game_sanction::{apply_sanctions, get_round_sanctions, GameSanction};
+use crate::quota_vote::{decide_quota, get_round_votes, QuotaVote};
+use crate::game_session::{GameParams, GameScores, GameSession, GameSignal, SessionState, SignalPayloadGameOver, SignalPayloadNextRound, SignalPayloadQuotaDecided, SignalPayloadStartSanctions};
+use crate::fixed_point::Fixed;
+use crate::leaderboard::index_scores;
+use crate::reputation::starting_stats;
+use crate::spectators::signal_recipients;
+use crate::session_control::ensure_session_running;
+use crate::types::{PlayerStat, PlayerStats, ReputationAmount, ResourceAmount, ResourcePools};
+use crate::utils::{convert_keys_from_b64, entry_hash_from_element, try_from_element, try_get_and_convert, try_get_by_header_and_convert, try_get_game_moves, try_get_latest_element};
 use hdk::prelude::*;
 use holo_hash::*;
 use std::collections::HashMap;
 use std::any::type_name;
 
-const NO_REPUTATION: ReputationAmount = 0;
-
-
 #[hdk_entry(id = "game_round", visibility = "public")]
 #[derive(PartialEq, Eq)]
 pub struct GameRound {
     pub round_state:RoundState,
     pub round_num: u32,
     pub session_header_hash: HeaderHashB64,
-    pub resources_left: ResourceAmount,
+    pub resources_left: ResourcePools,
+    /// what every player holds, added up over all rounds so far
     pub player_stats: PlayerStats,
     pub player_moves: Vec<EntryHash>,
+    /// sanctions made in the sanction phase of this round, if the session has one
+    pub player_sanctions: Vec<EntryHash>,
+    /// cap per player and resource pool decided by the votes, if the session has quota voting
+    pub quota: Option<ResourcePools>,
+    pub player_votes: Vec<EntryHash>,
+    /// shock that hit the regrowth at the end of this round, if any
+    pub shock: Option<Shock>,
 }
 
-impl GameRound {}
+impl GameRound {
+    /// First version of a round, starting from what the round before it finished with,
+    /// or for the first round from the start resources and stats of the session
+    pub fn start(params: &GameParams, round_num: u32, session_header_hash: HeaderHashB64, resources_left: ResourcePools, player_stats: PlayerStats) -> GameRound {
+        GameRound {
+            round_state: RoundState::first_of_round(params),
+            round_num,
+            session_header_hash,
+            resources_left,
+            player_stats,
+            player_moves: vec![],
+            player_sanctions: vec![],
+            quota: None,
+            player_votes: vec![],
+            shock: None,
+        }
+    }
+}
 
 impl Clone for GameRound {
     fn clone(&self) -> Self {
@@ -29,9 +60,13 @@ impl Clone for GameRound {
             round_state: self.round_state,
             round_num: self.round_num,
             session_header_hash: self.session_header_hash.clone(),
-            resources_left: self.resources_left,
+            resources_left: self.resources_left.clone(),
             player_stats: self.player_stats.clone(),
             player_moves: vec![],
+            player_sanctions: self.player_sanctions.clone(),
+            quota: self.quota.clone(),
+            player_votes: self.player_votes.clone(),
+            shock: self.shock,
         }
     }
 }
@@ -39,10 +74,59 @@ impl Clone for GameRound {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, SerializedBytes)]
 pub enum RoundState {
+    /// players vote on the quota before they make their moves,
+    /// only used when the session has quota voting enabled
+    Voting,
     InProgress,
+    /// all moves are made and players can sanction each other,
+    /// only used when the session has sanctions enabled
+    Sanctioning,
     Finished,
 }
 
+/// Who can close the rounds of a session. Closing a round means writing every version of
+/// the round after its first one, and the next round or the scores after the last one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClosingPolicy {
+    /// any agent, players race to close the round
+    Anyone,
+    /// only the owner of the session
+    Owner,
+    /// any player of the session
+    AnyPlayer,
+    /// one player per round, taking turns in the order of the players of the session
+    Rotating,
+}
+
+impl ClosingPolicy {
+    pub fn may_close(&self, game_session: &GameSession, round_num: u32, agent: &AgentPubKeyB64) -> bool {
+        match self {
+            ClosingPolicy::Anyone => true,
+            ClosingPolicy::Owner => &game_session.owner == agent,
+            ClosingPolicy::AnyPlayer => game_session.players.contains(agent),
+            ClosingPolicy::Rotating => Self::rotating_closer(game_session, round_num) == Some(agent),
+        }
+    }
+
+    /// Player that closes the round with the Rotating policy, rounds are numbered from 1
+    pub fn rotating_closer(game_session: &GameSession, round_num: u32) -> Option<&AgentPubKeyB64> {
+        match game_session.players.len() {
+            0 => None,
+            seats => game_session.players.get((round_num.saturating_sub(1) as usize) % seats),
+        }
+    }
+}
+
+impl RoundState {
+    /// State a new round starts in
+    pub fn first_of_round(params: &GameParams) -> RoundState {
+        match params.quota_voting {
+            Some(_) => RoundState::Voting,
+            None => RoundState::InProgress,
+        }
+    }
+}
+
 
 /*
 validation rules:
@@ -62,23 +146,41 @@ validate_update_entry_game_round_results -> EntryID
 
 // NOTE: this fn would be used both in validation and when creating game round entries
 // so it has to be very lightweight and can not make any DHT queries
-pub fn calculate_round_state(params: GameParams, player_moves: Vec<GameMove>) -> (ResourceAmount, PlayerStats) {
-    // todo:
-    // calculate round state from the player moves
-
-    // resources
-    let consumed_resources_in_round: i32 = player_moves.iter().map(|x| x.resources).sum();
-    let total_leftover_resource = params.start_amount - consumed_resources_in_round;
+// All amounts are Fixed, so the result is the same on every agent.
+/// The stats of a round build on the stats the round started with: what a player takes is
+/// added to what they already hold, and reputation and sanctions carry over.
+pub fn calculate_round_state(params: &GameParams, resources_at_start: &ResourcePools, previous_stats: &PlayerStats, player_moves: Vec<GameMove>, shock: Option<Shock>) -> (ResourcePools, PlayerStats) {
+    // resources, every pool is consumed and regrows on its own
+    let mut total_leftover_resources = ResourcePools::new();
+    for pool in params.resources.iter() {
+        let at_start = resources_at_start.get(&pool.name).copied().unwrap_or(Fixed::ZERO);
+        let consumed_resources_in_round: ResourceAmount = player_moves.iter().map(|x| x.amount_of(&pool.name)).sum();
+        let leftover_resource = at_start - consumed_resources_in_round;
+        // what is left regrows before the next round
+        let total_leftover_resource = pool.regeneration.regenerate_with_shock(leftover_resource, shock);
+        total_leftover_resources.insert(pool.name.clone(), total_leftover_resource);
+    }
 
-    // player stats
-    let mut stats: HashMap<AgentPubKeyB64, (ResourceAmount, ReputationAmount)> = HashMap::new();
+    // player stats, broken down by resource pool
+    let mut stats: PlayerStats = previous_stats.clone();
+    for stat in stats.values_mut() {
+        stat.last_taken = ResourcePools::new();
+    }
     for p in player_moves.iter() {
         let a = AgentPubKeyB64::from(p.owner.clone());
-        let tuple: (ResourceAmount, ReputationAmount) = (p.resources, NO_REPUTATION);
-        stats.insert(a, tuple);
+        let taken: ResourcePools = params
+            .resources
+            .iter()
+            .map(|pool| (pool.name.clone(), p.amount_of(&pool.name)))
+            .collect();
+        let stat = stats.entry(a).or_default();
+        for (name, amount) in taken.iter() {
+            *stat.resources.entry(name.clone()).or_insert(Fixed::ZERO) += *amount;
+        }
+        stat.last_taken = taken;
     }
 
-    (total_leftover_resource, stats)
+    (total_leftover_resources, stats)
 }
 
 // NOTE: game round is always created once players made their moves, so every round is always
@@ -105,6 +207,21 @@ pub fn calculate_round_state(params: GameParams, player_moves: Vec<GameMove>) ->
 // would actually be a game session entry) and attempt to close the current round by creating it's entry.
 // This would solely depend on the amount of moves retrieved being equal to the amount of players in the game
 pub fn try_to_close_round(current_round_header_hash: HeaderHash) -> ExternResult<HeaderHashB64> {
+    let (closed, mut next_phase) = close_round(current_round_header_hash)?;
+    // whoever closes a phase plays the bot seats in the next one, and closes the phases
+    // the bots complete on their own, so a session with only bots is played to the end
+    while let Some(round_header_hash) = next_phase {
+        if !play_bot_seats(round_header_hash.clone())? {
+            break;
+        }
+        next_phase = close_round(round_header_hash)?.1;
+    }
+    Ok(closed)
+}
+
+/// Closes the phase the round is at. Returns the new round version, next round or game scores,
+/// and the round whose next phase has started, if any.
+fn close_round(current_round_header_hash: HeaderHash) -> ExternResult<(HeaderHashB64, Option<HeaderHash>)> {
     println!("try to close round");
 
     println!("fetching data");
@@ -115,90 +232,195 @@ pub fn try_to_close_round(current_round_header_hash: HeaderHash) -> ExternResult
     };
     let current_round_entry_hash: EntryHash = entry_hash_from_element(current_round_element.clone())?;
     let mut current_round: GameRound = try_from_element(current_round_element.clone())?;
-
+    // the round entry is updated when the sanction phase starts, so check where it is at
+    let latest_round: GameRound = try_from_element(try_get_latest_element(current_round_entry_hash.clone())?)?;
+    ensure_session_running(current_round.session_header_hash.clone())?;
     
     // get current game_session
     let game_session_element = match get(HeaderHash::from(current_round.session_header_hash.clone()), GetOptions::content())? {
         Some(element) => element,
         None => return Err(WasmError::Guest("Current round not found".into())),
     };
-    let game_session: GameSession = get_game_session(current_round.session_header_hash.into());
-    let game_session_header_hash: HeaderHash = current_round_element.header_address().clone();
+    let game_session: GameSession = get_game_session(current_round.session_header_hash.clone().into());
+    let game_session_header_hash: HeaderHash = current_round.session_header_hash.clone().into();
     let game_session_entry_hash = entry_hash_from_element(game_session_element)?;
-    
-    // get game moves
-    let links = get_links(current_round_entry_hash.clone(), Some(LinkTag::new("game_move")))?;
-    let mut moves: Vec<GameMove> = vec![];
-    for link in links.into_inner() {
-        let item: GameMove = match get(link.target.clone(), GetOptions::default())? {
-            Some(x) => {
-                let g:GameMove = try_from_element(x)?;
-                g
-            }
-            None => return Err(WasmError::Guest("Cannot extract game move from link".into())),
-        };
-        moves.push(item);
+    let me = AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey);
+    if !game_session.game_params.closing_policy.may_close(&game_session, current_round.round_num, &me) {
+        return Err(WasmError::Guest("Cannot close round: the closing policy of this session doesn't allow you".into()));
     }
-    println!("all data fetched");
-    println!("****************");
 
-    println!("check number of moves");
-    println!("moves list #{:?}", moves);
-    let moves_len = moves.len();
-    if moves_len < game_session.players.len() {
-        println!("number of moves found: #{:?}", moves_len);
-        return Err(WasmError::Guest("Cannot close round: wait until all moves are made".into()));
+    let quota = latest_round.quota.clone();
+    let player_votes = latest_round.player_votes.clone();
+    let (resources_left, stats, player_moves, player_sanctions, shock) = match latest_round.round_state {
+        RoundState::Finished => {
+            return Err(WasmError::Guest("Cannot close round: round is already finished".into()));
+        }
+        RoundState::Voting => {
+            let (votes, vote_entry_hashes) = get_round_votes(current_round_entry_hash.clone())?;
+            debug!("number of votes found: #{:?}", votes.len());
+            if votes.len() < game_session.players.len() {
+                return Err(WasmError::Guest("Cannot close voting: wait until all players voted".into()));
+            }
+            let rule = match game_session.game_params.quota_voting {
+                Some(rule) => rule,
+                None => return Err(WasmError::Guest("Quota voting is not enabled for this session".into())),
+            };
+            let quota = decide_quota(rule, game_session.game_params.resource_names(), &votes);
+            debug!("quota decided: {:?}", quota);
+            let voted_round = GameRound {
+                round_state: RoundState::InProgress,
+                round_num: current_round.round_num,
+                session_header_hash: game_session_header_hash.clone().into(),
+                resources_left: current_round.resources_left.clone(),
+                player_stats: current_round.player_stats.clone(),
+                player_moves: vec![],
+                player_sanctions: vec![],
+                quota: Some(quota.clone()),
+                player_votes: vote_entry_hashes,
+                shock: None,
+            };
+            let voted_round_header_hash = update_entry(current_round_header_hash.clone(), &voted_round)?;
+
+            let signal_payload = SignalPayloadQuotaDecided {
+                game_session_header_hash: game_session_header_hash.into(),
+                current_round_header_hash: current_round_header_hash.clone().into(),
+                quota,
+            };
+            let signal = ExternIO::encode(GameSignal::QuotaDecided(signal_payload))?;
+            let recipients = signal_recipients(&game_session)?;
+            debug!("sending signal to {:?}", recipients);
+            remote_signal(signal, recipients)?;
+            return Ok((voted_round_header_hash.into(), Some(current_round_header_hash)));
+        }
+        RoundState::InProgress => {
+            // get game moves, players can update their move until the round is closed
+            // so we always use the latest version of every move
+            let links = get_links(current_round_entry_hash.clone(), Some(LinkTag::new("game_move")))?;
+            let mut moves: Vec<GameMove> = vec![];
+            let mut move_entry_hashes: Vec<EntryHash> = vec![];
+            for link in links.into_inner() {
+                let element = try_get_latest_element(link.target.clone())?;
+                move_entry_hashes.push(entry_hash_from_element(element.clone())?);
+                let item: GameMove = try_from_element(element)?;
+                moves.push(item);
+            }
+            println!("all data fetched");
+            println!("****************");
+
+            println!("check number of moves");
+            println!("moves list #{:?}", moves);
+            let moves_len = moves.len();
+            if moves_len < game_session.players.len() {
+                println!("number of moves found: #{:?}", moves_len);
+                return Err(WasmError::Guest("Cannot close round: wait until all moves are made".into()));
+            };
+            // TODO add check is no player made 2 moves. all move need unique owners
+
+            println!("****************");
+            println!("all players made their moves: calculating round state");
+            let shock = game_session
+                .game_params
+                .shocks
+                .and_then(|shocks| shocks.draw(&mut shock_rng(&game_session_entry_hash, &current_round_entry_hash)));
+            let (resources_left, stats) = calculate_round_state(&game_session.game_params, &current_round.resources_left, &current_round.player_stats, moves, shock);
+
+            if game_session.game_params.sanctions.is_some() {
+                debug!("starting sanction phase");
+                let sanctioning_round = GameRound {
+                    round_state: RoundState::Sanctioning,
+                    round_num: current_round.round_num,
+                    session_header_hash: game_session_header_hash.clone().into(),
+                    resources_left,
+                    player_stats: stats,
+                    player_moves: move_entry_hashes,
+                    player_sanctions: vec![],
+                    quota: quota.clone(),
+                    player_votes: player_votes.clone(),
+                    shock,
+                };
+                let sanctioning_round_header_hash = update_entry(current_round_header_hash.clone(), &sanctioning_round)?;
+
+                let signal_payload = SignalPayloadStartSanctions {
+                    game_session_header_hash: game_session_header_hash.into(),
+                    current_round_header_hash: current_round_header_hash.clone().into(),
+                    current_round: sanctioning_round,
+                };
+                let signal = ExternIO::encode(GameSignal::StartSanctions(signal_payload))?;
+                let recipients = signal_recipients(&game_session)?;
+                debug!("sending signal to {:?}", recipients);
+                remote_signal(signal, recipients)?;
+                return Ok((sanctioning_round_header_hash.into(), Some(current_round_header_hash)));
+            }
+            (resources_left, stats, move_entry_hashes, vec![], shock)
+        }
+        RoundState::Sanctioning => {
+            let (sanctions, sanction_entry_hashes) = get_round_sanctions(current_round_entry_hash.clone())?;
+            debug!("number of sanctions found: #{:?}", sanctions.len());
+            if sanctions.len() < game_session.players.len() {
+                return Err(WasmError::Guest("Cannot close round: wait until all players decided on sanctions".into()));
+            }
+            let sanction_params = match game_session.game_params.sanctions {
+                Some(params) => params,
+                None => return Err(WasmError::Guest("Sanctions are not enabled for this session".into())),
+            };
+            let stats = apply_sanctions(sanction_params, latest_round.player_stats, &sanctions);
+            (latest_round.resources_left, stats, latest_round.player_moves, sanction_entry_hashes, latest_round.shock)
+        }
     };
-    // TODO add check is no player made 2 moves. all move need unique owners
-
-    
-    println!("****************");
-    println!("all players made their moves: calculating round state");
-    let (resources_left, stats) = calculate_round_state(game_session.game_params, moves);
     
     // complete round state and update round entry
     let updated_current_round = GameRound{
         round_state: RoundState::Finished,
         round_num:current_round.round_num,
         session_header_hash: game_session_header_hash.clone().into(),
-        resources_left: resources_left,
+        resources_left: resources_left.clone(),
         player_stats: stats.clone(),
-        player_moves: current_round.player_moves.clone(),  
+        player_moves,
+        player_sanctions,
+        quota,
+        player_votes,
+        shock,
     };
 
+    // NOTE: we always update the round as it was created, so every version of the round
+    // is an update of the original entry that the moves and sanctions are linked from
     let updated_current_round_header_hash = update_entry(current_round_header_hash, updated_current_round)?;
 
     // decide what to do next
     // - continue game, start next round
-    // - end game, because resources are depleted
+    // - end game, because one of the resource pools is depleted
     // - end game, because all rounds are played
-    if (current_round.round_num < game_session.game_params.num_rounds || resources_left > 0){ // can start next round?
+    let all_pools_left = resources_left.values().all(|amount| amount.is_positive());
+    if current_round.round_num < game_session.game_params.num_rounds && all_pools_left { // can start next round?
         println!("continue: creating next round");
         // TODO: instead of creating a new entry, we should continue the update chain
         // from the previous round entry hash and commit an updated version
-        let next_round = GameRound {
-            round_num: current_round.round_num + 1,
-            round_state: RoundState::InProgress,
-            session_header_hash: game_session_header_hash.clone().into(),
-            resources_left: resources_left,
-            player_stats: stats,
-            player_moves: vec![],
-        };
+        let next_round = GameRound::start(
+            &game_session.game_params,
+            current_round.round_num + 1,
+            game_session_header_hash.clone().into(),
+            resources_left,
+            stats,
+        );
+        // NOTE: validation finds the finished round through the previous header of the
+        // next round, so nothing can be written in between
         let next_round_header_hash = create_entry(&next_round)?;
         let next_round_entry_hash = hash_entry(&next_round)?;
+        create_link(game_session_entry_hash.clone(), next_round_entry_hash.clone(), LinkTag::new("game_round"))?;
 
         let signal_payload = SignalPayloadNextRound {
             game_session_header_hash: HeaderHashB64::from(game_session_header_hash), 
             current_round_header_hash: HeaderHashB64::from(updated_current_round_header_hash),
             next_round_header_hash: next_round_header_hash.clone().into(),
+            next_round,
         };
         let signal = ExternIO::encode(GameSignal::NextRound(signal_payload))?;
         // Since we're storing agent keys as AgentPubKeyB64, and remote_signal only accepts
         // the AgentPubKey type, we need to convert our keys to the expected data type
-        remote_signal(signal, convert_keys_from_b64(game_session.players.clone()))?;
+        remote_signal(signal, signal_recipients(&game_session)?)?;
         println!("sending signal to {:?}", game_session.players.clone());
 
-        Ok(next_round_header_hash.into())
+        Ok((next_round_header_hash.clone().into(), Some(next_round_header_hash)))
 
     } else {
         // distinction between game ended because all rounds completed of all resources depleted can be easily made in frontend
@@ -207,6 +429,8 @@ pub fn try_to_close_round(current_round_header_hash: HeaderHash) -> ExternResult
         let game_scores = GameScores{
             session: EntryHashB64::from(game_session_entry_hash.clone()),
             stats: stats,
+            last_round: updated_current_round_header_hash.into(),
+            sustainability: game_session.game_params.sustainability(&resources_left),
         };
         let game_scores_header_hash = create_entry(&game_scores)?;
         let game_scores_entry_hash = hash_entry(&game_scores)?;
@@ -217,20 +441,258 @@ pub fn try_to_close_round(current_round_header_hash: HeaderHash) -> ExternResult
             game_scores_entry_hash.clone(),
             LinkTag::new("game_scores"),
         )?;
+        // and make them show up in the career stats and leaderboards
+        index_scores(&game_scores, game_scores_entry_hash)?;
         // prepare signal
         let signal_payload = SignalPayloadGameOver{
             game_scores: game_scores,
         };
         // send signal
         let signal = ExternIO::encode(GameSignal::GameOver(signal_payload))?;
-        remote_signal(signal, convert_keys_from_b64(game_session.players.clone()))?;
+        remote_signal(signal, signal_recipients(&game_session)?)?;
         println!("sending signal to {:?}", game_session.players.clone());
         // return hash of scores
-        Ok(game_scores_header_hash.into())
+        Ok((game_scores_header_hash.into(), None))
+    }
+}
+
+
+
+/// Closing a round is an update of the round entry that lists the versions of the moves it
+/// was calculated from, the closer picks the latest ones it can see. The round is the source
+/// of truth for which versions count: validation recalculates it from exactly those moves,
+/// and for a finished round of a session with sanctions, from the sanctions it lists.
+/// In sessions with quota voting every update also has to carry the quota the votes decided.
+/// Only agents the closing policy of the session allows can update a round.
+#[hdk_extern]
+pub fn validate_update_entry_game_round(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let game_round: GameRound = try_from_element(data.element.clone())?;
+    if game_round.round_state == RoundState::Voting {
+        return Ok(ValidateCallbackResult::Invalid("A round can't go back to voting".into()));
+    }
+    let (original_round_header_hash, original_round_entry_hash) = match data.element.header() {
+        Header::Update(update) => (update.original_header_address.clone(), update.original_entry_address.clone()),
+        _ => return Ok(ValidateCallbackResult::Invalid("Expected an update header".into())),
+    };
+    let original_round: GameRound = match get(original_round_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![original_round_header_hash.into()])),
+    };
+    // the closing policy is read from the session of the original round
+    if game_round.session_header_hash != original_round.session_header_hash || game_round.round_num != original_round.round_num {
+        return Ok(ValidateCallbackResult::Invalid("An updated round must keep its session and number".into()));
+    }
+
+    let session_header_hash: HeaderHash = game_round.session_header_hash.clone().into();
+    let game_session_element = match get(session_header_hash.clone(), GetOptions::content())? {
+        Some(element) => element,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![session_header_hash.into()])),
+    };
+    let game_session_entry_hash = entry_hash_from_element(game_session_element.clone())?;
+    let game_session: GameSession = try_from_element(game_session_element)?;
+    let author = AgentPubKeyB64::from(data.element.header().author().clone());
+    if !game_session.game_params.closing_policy.may_close(&game_session, original_round.round_num, &author) {
+        return Ok(ValidateCallbackResult::Invalid("Round was closed by an agent the closing policy doesn't allow".into()));
+    }
+
+    match game_session.game_params.quota_voting {
+        Some(rule) => {
+            let mut votes: Vec<QuotaVote> = vec![];
+            for vote_entry_hash in game_round.player_votes.iter() {
+                let quota_vote: QuotaVote = match get(vote_entry_hash.clone(), GetOptions::content())? {
+                    Some(element) => try_from_element(element)?,
+                    None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![vote_entry_hash.clone().into()])),
+                };
+                if quota_vote.round != original_round_entry_hash {
+                    return Ok(ValidateCallbackResult::Invalid("Round contains a vote from another round".into()));
+                }
+                if votes.iter().any(|v: &QuotaVote| v.owner == quota_vote.owner) {
+                    return Ok(ValidateCallbackResult::Invalid("Round contains more than one vote of a player".into()));
+                }
+                votes.push(quota_vote);
+            }
+            if votes.len() < game_session.players.len() {
+                return Ok(ValidateCallbackResult::Invalid("Quota was decided before all players voted".into()));
+            }
+            if Some(decide_quota(rule, game_session.game_params.resource_names(), &votes)) != game_round.quota {
+                return Ok(ValidateCallbackResult::Invalid("Round quota doesn't match the votes".into()));
+            }
+        }
+        None => {
+            if game_round.quota.is_some() || !game_round.player_votes.is_empty() {
+                return Ok(ValidateCallbackResult::Invalid("Quota voting is not enabled for this session".into()));
+            }
+        }
+    }
+    if game_round.round_state == RoundState::InProgress {
+        // the only update that keeps a round in progress is the one that records its quota,
+        // it can't change anything else the moves are made against
+        if game_session.game_params.quota_voting.is_none() {
+            return Ok(ValidateCallbackResult::Invalid("Only the quota of a round can be decided while it's in progress".into()));
+        }
+        if game_round.resources_left != original_round.resources_left
+            || game_round.player_stats != original_round.player_stats
+            || !game_round.player_moves.is_empty()
+            || !game_round.player_sanctions.is_empty()
+            || game_round.shock.is_some()
+        {
+            return Ok(ValidateCallbackResult::Invalid("Deciding the quota can't change the state of the round".into()));
+        }
+        return Ok(ValidateCallbackResult::Valid);
+    }
+
+    let mut moves: Vec<GameMove> = vec![];
+    for move_entry_hash in game_round.player_moves.iter() {
+        let game_move: GameMove = match get(move_entry_hash.clone(), GetOptions::content())? {
+            Some(element) => try_from_element(element)?,
+            None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![move_entry_hash.clone().into()])),
+        };
+        if game_move.round != original_round_entry_hash {
+            return Ok(ValidateCallbackResult::Invalid("Round contains a move from another round".into()));
+        }
+        if moves.iter().any(|m: &GameMove| m.owner == game_move.owner) {
+            return Ok(ValidateCallbackResult::Invalid("Round contains more than one move of a player".into()));
+        }
+        moves.push(game_move);
+    }
+    if moves.len() < game_session.players.len() {
+        return Ok(ValidateCallbackResult::Invalid("Round was closed before all moves were made".into()));
     }
+
+    // the shock is drawn from the same seed as when closing, so it has to come out the same
+    let shock = game_session
+        .game_params
+        .shocks
+        .and_then(|shocks| shocks.draw(&mut shock_rng(&game_session_entry_hash, &original_round_entry_hash)));
+    if shock != game_round.shock {
+        return Ok(ValidateCallbackResult::Invalid("Round shock doesn't match the drawn shock".into()));
+    }
+    let (resources_left, mut stats) = calculate_round_state(&game_session.game_params, &original_round.resources_left, &original_round.player_stats, moves, shock);
+
+    match (game_round.round_state, game_session.game_params.sanctions) {
+        (RoundState::Sanctioning, None) => {
+            return Ok(ValidateCallbackResult::Invalid("Sanctions are not enabled for this session".into()));
+        }
+        (RoundState::Sanctioning, Some(_)) => {
+            if !game_round.player_sanctions.is_empty() {
+                return Ok(ValidateCallbackResult::Invalid("Sanction phase can't start with sanctions".into()));
+            }
+        }
+        (RoundState::Finished, Some(sanction_params)) => {
+            let mut sanctions: Vec<GameSanction> = vec![];
+            for sanction_entry_hash in game_round.player_sanctions.iter() {
+                let game_sanction: GameSanction = match get(sanction_entry_hash.clone(), GetOptions::content())? {
+                    Some(element) => try_from_element(element)?,
+                    None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![sanction_entry_hash.clone().into()])),
+                };
+                if game_sanction.round != original_round_entry_hash {
+                    return Ok(ValidateCallbackResult::Invalid("Round contains a sanction from another round".into()));
+                }
+                if sanctions.iter().any(|s: &GameSanction| s.owner == game_sanction.owner) {
+                    return Ok(ValidateCallbackResult::Invalid("Round contains more than one sanction of a player".into()));
+                }
+                sanctions.push(game_sanction);
+            }
+            if sanctions.len() < game_session.players.len() {
+                return Ok(ValidateCallbackResult::Invalid("Round was closed before all sanctions were made".into()));
+            }
+            stats = apply_sanctions(sanction_params, stats, &sanctions);
+        }
+        _ => {}
+    }
+
+    if resources_left != game_round.resources_left || stats != game_round.player_stats {
+        return Ok(ValidateCallbackResult::Invalid("Round state doesn't match the moves made".into()));
+    }
+    Ok(ValidateCallbackResult::Valid)
 }
 
+/// The first round is created with the session, by the agent that created the session, and
+/// starts from the start resources of the session and the stats from starting_stats.
+/// Every later round is created when the round before it is closed, so its author has to
+/// be allowed to close that round, and it starts from what that round finished with.
+#[hdk_extern]
+pub fn validate_create_entry_game_round(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let game_round: GameRound = try_from_element(data.element.clone())?;
+    let session_header_hash: HeaderHash = game_round.session_header_hash.clone().into();
+    let game_session_element = match get(session_header_hash.clone(), GetOptions::content())? {
+        Some(element) => element,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![session_header_hash.into()])),
+    };
+    let session_author = game_session_element.header().author().clone();
+    let game_session: GameSession = try_from_element(game_session_element)?;
+    let params = &game_session.game_params;
+    if game_round.round_num < 1 {
+        return Ok(ValidateCallbackResult::Invalid("Rounds are numbered from 1".into()));
+    }
+
+    if game_round.round_num == 1 {
+        if data.element.header().author() != &session_author {
+            return Ok(ValidateCallbackResult::Invalid("The first round can only be started by the author of its session".into()));
+        }
+        let mut inherited: Vec<GameScores> = vec![];
+        for scores_hash in game_session.inherited_scores.iter() {
+            let scores_entry_hash: EntryHash = scores_hash.clone().into();
+            match get(scores_entry_hash.clone(), GetOptions::content())? {
+                Some(element) => inherited.push(try_from_element(element)?),
+                None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![scores_entry_hash.into()])),
+            }
+        }
+        let first_round = GameRound::start(
+            params,
+            1,
+            game_round.session_header_hash.clone(),
+            params.start_resources(),
+            starting_stats(&game_session, &inherited),
+        );
+        if game_round != first_round {
+            return Ok(ValidateCallbackResult::Invalid("The first round has to start from the start of its session".into()));
+        }
+        return Ok(ValidateCallbackResult::Valid);
+    }
 
+    let author = AgentPubKeyB64::from(data.element.header().author().clone());
+    if !params.closing_policy.may_close(&game_session, game_round.round_num - 1, &author) {
+        return Ok(ValidateCallbackResult::Invalid("Round was started by an agent the closing policy doesn't allow".into()));
+    }
+    // the round before is closed right before this one is created, see try_to_close_round
+    let previous_header_hash = match data.element.header().prev_header() {
+        Some(previous_header_hash) => previous_header_hash.clone(),
+        None => return Ok(ValidateCallbackResult::Invalid("Round has to follow the round before it".into())),
+    };
+    let previous_element = match get(previous_header_hash.clone(), GetOptions::content())? {
+        Some(element) => element,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![previous_header_hash.into()])),
+    };
+    let previous_round: GameRound = match previous_element.header() {
+        Header::Update(_) => match try_from_element(previous_element) {
+            Ok(previous_round) => previous_round,
+            Err(_) => return Ok(ValidateCallbackResult::Invalid("Round has to follow the round before it".into())),
+        },
+        _ => return Ok(ValidateCallbackResult::Invalid("Round has to follow the round before it".into())),
+    };
+    if previous_round.session_header_hash != game_round.session_header_hash
+        || previous_round.round_num != game_round.round_num - 1
+        || previous_round.round_state != RoundState::Finished
+    {
+        return Ok(ValidateCallbackResult::Invalid("Round has to follow the finished round before it".into()));
+    }
+    let all_pools_left = previous_round.resources_left.values().all(|amount| amount.is_positive());
+    if previous_round.round_num >= params.num_rounds || !all_pools_left {
+        return Ok(ValidateCallbackResult::Invalid("Game is already over".into()));
+    }
+    let next_round = GameRound::start(
+        params,
+        game_round.round_num,
+        game_round.session_header_hash.clone(),
+        previous_round.resources_left,
+        previous_round.player_stats,
+    );
+    if game_round != next_round {
+        return Ok(ValidateCallbackResult::Invalid("Round has to start from what the round before it finished with".into()));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
 
 fn extract_moves(links: Links, game_session: &GameSession) -> Vec<GameMove> {
     let links_vec = links.into_inner();
@@ -713,3 +1175,64 @@ fn get_all_round_moves(round_entry_hash: EntryHash) {
 //         assert_eq!(stats_p1.1, 0);
 //     }
 // }
+
+#[cfg(test)]
+mod round_state_tests {
+    use super::*;
+    use crate::regeneration::RegenerationModel;
+    use crate::types::new_player_stats;
+
+    fn params() -> GameParams {
+        GameParams::single_pool("fish", Fixed::from_int(100), RegenerationModel::Exponential { rate: Fixed::ZERO })
+    }
+
+    fn agent(seat: u8) -> AgentPubKey {
+        AgentPubKey::from_raw_36(vec![seat; 36])
+    }
+
+    fn take(seat: u8, amount: i64) -> GameMove {
+        GameMove {
+            owner: agent(seat),
+            round: EntryHash::from_raw_36(vec![0; 36]),
+            resources: vec![("fish".to_string(), Fixed::from_int(amount))].into_iter().collect(),
+            quota_header_hash: None,
+        }
+    }
+
+    fn stat_of(stats: &PlayerStats, seat: u8) -> PlayerStat {
+        stats[&AgentPubKeyB64::from(agent(seat))].clone()
+    }
+
+    #[test]
+    fn stats_add_up_over_rounds() {
+        let params = params();
+        let start = new_player_stats(vec![agent(1).into(), agent(2).into()], params.resource_names());
+        let (left, stats) = calculate_round_state(&params, &params.start_resources(), &start, vec![take(1, 10), take(2, 20)], None);
+        assert_eq!(left["fish"], Fixed::from_int(70));
+        let (left, stats) = calculate_round_state(&params, &left, &stats, vec![take(1, 5), take(2, 0)], None);
+        assert_eq!(left["fish"], Fixed::from_int(65));
+
+        let first = stat_of(&stats, 1);
+        assert_eq!(first.resources["fish"], Fixed::from_int(15));
+        assert_eq!(first.last_taken["fish"], Fixed::from_int(5));
+        let second = stat_of(&stats, 2);
+        assert_eq!(second.resources["fish"], Fixed::from_int(20));
+        assert_eq!(second.last_taken["fish"], Fixed::ZERO);
+        // the final score counts everything a player holds, not only the last round
+        assert_eq!(params.score(&first), Fixed::from_int(15));
+    }
+
+    #[test]
+    fn inherited_reputation_is_carried_forward() {
+        let params = params();
+        // start_session seeds round one with the career reputation of every player
+        let mut start = new_player_stats(vec![agent(1).into(), agent(2).into()], params.resource_names());
+        start.get_mut(&AgentPubKeyB64::from(agent(1))).unwrap().reputation = 4;
+        let (left, stats) = calculate_round_state(&params, &params.start_resources(), &start, vec![take(1, 10), take(2, 10)], None);
+        let (_, stats) = calculate_round_state(&params, &left, &stats, vec![take(1, 10), take(2, 10)], None);
+
+        assert_eq!(stat_of(&stats, 1).reputation, 4);
+        assert_eq!(stat_of(&stats, 2).reputation, 0);
+        assert_eq!(params.score(&stat_of(&stats, 1)), Fixed::from_int(24));
+    }
+}
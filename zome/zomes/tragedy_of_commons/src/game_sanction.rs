@@ -0,0 +1,304 @@
+use crate::{
+    bots::{bot_seat, may_play_for},
+    fixed_point::Fixed,
+    game_round::{GameRound, RoundState},
+    game_session::GameSession,
+    session_control::ensure_session_running,
+    types::{PlayerStats, ReputationAmount, ResourceAmount},
+    utils::{entry_hash_from_element, try_from_element, try_get_latest_element},
+};
+use hdk::prelude::*;
+use holo_hash::*;
+
+/// Ratios that decide how hard a sanction hits, per unit of resources spent on it
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SanctionParams {
+    /// resources the target loses for every unit the sanctioning player spends
+    pub resource_ratio: Fixed,
+    /// reputation the target loses for every unit the sanctioning player spends,
+    /// the result is rounded down to a whole reputation point
+    pub reputation_ratio: Fixed,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SanctionKind {
+    Resources,
+    Reputation,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Sanction {
+    pub target: AgentPubKeyB64,
+    /// resource pool the sanctioning player pays from, and that a Resources sanction cuts
+    pub resource: String,
+    pub spent: ResourceAmount,
+    pub kind: SanctionKind,
+}
+
+/// Every player makes exactly one GameSanction in the sanction phase of a round,
+/// with an empty list of sanctions when they don't want to punish anybody.
+#[hdk_entry(id = "game_sanction", visibility = "public")]
+pub struct GameSanction {
+    pub owner: AgentPubKey,
+    pub round: EntryHash,
+    pub sanctions: Vec<Sanction>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct GameSanctionInput {
+    pub sanctions: Vec<Sanction>,
+    pub current_round_header_hash: HeaderHashB64,
+}
+
+/// Function to call once the round entered the sanction phase, see RoundState::Sanctioning
+#[hdk_extern]
+pub fn new_sanction(input: GameSanctionInput) -> ExternResult<HeaderHash> {
+    new_sanction_for(agent_info()?.agent_initial_pubkey, input)
+}
+
+/// Makes the sanction of the given seat, which is the caller itself unless it plays a bot seat
+pub fn new_sanction_for(owner: AgentPubKey, input: GameSanctionInput) -> ExternResult<HeaderHash> {
+    let game_round_header_hash: HeaderHash = input.current_round_header_hash.into();
+    let game_round_element = match get(game_round_header_hash, GetOptions::content())? {
+        Some(element) => element,
+        None => return Err(WasmError::Guest("Current round not found".into())),
+    };
+    let game_round_entry_hash = entry_hash_from_element(game_round_element)?;
+
+    let latest_round: GameRound = try_from_element(try_get_latest_element(game_round_entry_hash.clone())?)?;
+    if latest_round.round_state != RoundState::Sanctioning {
+        return Err(WasmError::Guest("Cannot sanction: round is not in the sanction phase".into()));
+    }
+    ensure_session_running(latest_round.session_header_hash.clone())?;
+
+    let (existing_sanctions, _) = get_round_sanctions(game_round_entry_hash.clone())?;
+    if existing_sanctions.iter().any(|s| s.owner == owner) {
+        return Err(WasmError::Guest("Cannot sanction: you already sanctioned in this round".into()));
+    }
+
+    let game_sanction = GameSanction {
+        owner,
+        round: game_round_entry_hash.clone(),
+        sanctions: input.sanctions,
+    };
+    let header_hash_game_sanction = create_entry(&game_sanction)?;
+    let entry_hash_game_sanction = hash_entry(&game_sanction)?;
+    create_link(
+        game_round_entry_hash,
+        entry_hash_game_sanction,
+        LinkTag::new("game_sanction"),
+    )?;
+    Ok(header_hash_game_sanction)
+}
+
+/// Retrieves the sanctions linked from a round, only the first one of every player counts
+pub fn get_round_sanctions(round_entry_hash: EntryHash) -> ExternResult<(Vec<GameSanction>, Vec<EntryHash>)> {
+    let links = get_links(round_entry_hash, Some(LinkTag::new("game_sanction")))?;
+    let mut sanctions: Vec<GameSanction> = vec![];
+    let mut sanction_entry_hashes: Vec<EntryHash> = vec![];
+    for link in links.into_inner() {
+        let item: GameSanction = match get(link.target.clone(), GetOptions::default())? {
+            Some(element) => try_from_element(element)?,
+            None => return Err(WasmError::Guest("Cannot extract game sanction from link".into())),
+        };
+        if sanctions.iter().any(|s| s.owner == item.owner) {
+            continue;
+        }
+        sanctions.push(item);
+        sanction_entry_hashes.push(link.target);
+    }
+    Ok((sanctions, sanction_entry_hashes))
+}
+
+/// Applies the sanctions in order to the stats of the round. A player can't spend more of a
+/// resource than they have left, and a target can't lose more resources than it has.
+pub fn apply_sanctions(params: SanctionParams, mut stats: PlayerStats, sanctions: &[GameSanction]) -> PlayerStats {
+    for game_sanction in sanctions {
+        let owner = AgentPubKeyB64::from(game_sanction.owner.clone());
+        for sanction in game_sanction.sanctions.iter() {
+            if sanction.target == owner || !stats.contains_key(&sanction.target) {
+                continue;
+            }
+            let available = match stats.get(&owner) {
+                Some(stat) => stat.resources.get(&sanction.resource).copied().unwrap_or(Fixed::ZERO),
+                None => continue,
+            };
+            let spent = std::cmp::min(sanction.spent, available);
+            if !spent.is_positive() {
+                continue;
+            }
+            if let Some(stat) = stats.get_mut(&owner) {
+                *stat.resources.entry(sanction.resource.clone()).or_insert(Fixed::ZERO) -= spent;
+                *stat.sanctions_spent.entry(sanction.resource.clone()).or_insert(Fixed::ZERO) += spent;
+            }
+            if let Some(target) = stats.get_mut(&sanction.target) {
+                match sanction.kind {
+                    SanctionKind::Resources => {
+                        let held = target.resources.get(&sanction.resource).copied().unwrap_or(Fixed::ZERO);
+                        let cut = std::cmp::min(spent * params.resource_ratio, std::cmp::max(held, Fixed::ZERO));
+                        *target.resources.entry(sanction.resource.clone()).or_insert(Fixed::ZERO) -= cut;
+                        *target.sanctioned_resources.entry(sanction.resource.clone()).or_insert(Fixed::ZERO) += cut;
+                    }
+                    SanctionKind::Reputation => {
+                        let cut = (spent * params.reputation_ratio).floor_int() as ReputationAmount;
+                        target.reputation -= cut;
+                        target.sanctioned_reputation += cut;
+                    }
+                }
+            }
+        }
+    }
+    stats
+}
+
+/// A sanction can only be made by a player of a session that has sanctions enabled,
+/// and only targets other players of that session.
+/// Sanctions of bot seats are made by the agent that plays the bots of the round,
+/// and are always empty.
+#[hdk_extern]
+pub fn validate_create_entry_game_sanction(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let game_sanction: GameSanction = try_from_element(data.element.clone())?;
+    let game_round: GameRound = match get(game_sanction.round.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![game_sanction.round.clone().into()])),
+    };
+    let session_header_hash: HeaderHash = game_round.session_header_hash.into();
+    let game_session: GameSession = match get(session_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![session_header_hash.into()])),
+    };
+
+    if game_session.game_params.sanctions.is_none() {
+        return Ok(ValidateCallbackResult::Invalid("Sanctions are not enabled for this session".into()));
+    }
+    let owner = AgentPubKeyB64::from(game_sanction.owner.clone());
+    if !game_session.players.contains(&owner) {
+        return Ok(ValidateCallbackResult::Invalid("Only players can sanction".into()));
+    }
+    if !may_play_for(&game_session, game_round.round_num, &game_sanction.owner, data.element.header().author()) {
+        return Ok(ValidateCallbackResult::Invalid("A sanction can only be made by its owner".into()));
+    }
+    if bot_seat(&game_session, &game_sanction.owner).is_some() && !game_sanction.sanctions.is_empty() {
+        return Ok(ValidateCallbackResult::Invalid("Bot seats don't sanction".into()));
+    }
+    for sanction in game_sanction.sanctions.iter() {
+        if sanction.target == owner || !game_session.players.contains(&sanction.target) {
+            return Ok(ValidateCallbackResult::Invalid("Sanctions can only target other players".into()));
+        }
+        if !sanction.spent.is_positive() {
+            return Ok(ValidateCallbackResult::Invalid("A sanction has to spend a positive amount".into()));
+        }
+        if !game_session.game_params.resources.iter().any(|r| r.name == sanction.resource) {
+            return Ok(ValidateCallbackResult::Invalid("A sanction has to spend an existing resource".into()));
+        }
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_move::GameMove;
+    use crate::game_round::calculate_round_state;
+    use crate::game_session::GameParams;
+    use crate::regeneration::RegenerationModel;
+    use crate::types::{new_player_stats, PlayerStat};
+
+    const PARAMS: SanctionParams = SanctionParams {
+        resource_ratio: Fixed::from_int(3),
+        reputation_ratio: Fixed::from_raw(1500),
+    };
+
+    fn agent(seat: u8) -> AgentPubKey {
+        AgentPubKey::from_raw_36(vec![seat; 36])
+    }
+
+    fn stat_of(stats: &PlayerStats, seat: u8) -> PlayerStat {
+        stats[&AgentPubKeyB64::from(agent(seat))].clone()
+    }
+
+    fn sanction(owner: u8, target: u8, spent: i64, kind: SanctionKind) -> GameSanction {
+        GameSanction {
+            owner: agent(owner),
+            round: EntryHash::from_raw_36(vec![0; 36]),
+            sanctions: vec![Sanction {
+                target: agent(target).into(),
+                resource: "fish".into(),
+                spent: Fixed::from_int(spent),
+                kind,
+            }],
+        }
+    }
+
+    fn holding(fish: i64) -> PlayerStats {
+        let mut stats = new_player_stats(vec![agent(1).into(), agent(2).into()], vec!["fish".into()]);
+        for stat in stats.values_mut() {
+            stat.resources.insert("fish".into(), Fixed::from_int(fish));
+        }
+        stats
+    }
+
+    #[test]
+    fn sanctions_cost_the_sanctioning_player_and_cut_the_target() {
+        let stats = apply_sanctions(
+            PARAMS,
+            holding(10),
+            &[sanction(1, 2, 2, SanctionKind::Resources), sanction(2, 1, 1, SanctionKind::Reputation)],
+        );
+        let first = stat_of(&stats, 1);
+        assert_eq!(first.resources["fish"], Fixed::from_int(8));
+        assert_eq!(first.sanctions_spent["fish"], Fixed::from_int(2));
+        // 1 * 1.5 is rounded down to a whole reputation point
+        assert_eq!(first.reputation, -1);
+        assert_eq!(first.sanctioned_reputation, 1);
+        let second = stat_of(&stats, 2);
+        // 10 - 2 * 3 lost, then 1 spent
+        assert_eq!(second.resources["fish"], Fixed::from_int(3));
+        assert_eq!(second.sanctioned_resources["fish"], Fixed::from_int(6));
+        assert_eq!(second.sanctions_spent["fish"], Fixed::from_int(1));
+    }
+
+    #[test]
+    fn sanctions_are_capped_by_what_players_hold() {
+        let stats = apply_sanctions(PARAMS, holding(2), &[sanction(1, 2, 5, SanctionKind::Resources)]);
+        // only 2 can be spent, and the target can't lose more than the 2 it holds
+        assert_eq!(stat_of(&stats, 1).resources["fish"], Fixed::ZERO);
+        assert_eq!(stat_of(&stats, 2).resources["fish"], Fixed::ZERO);
+        assert_eq!(stat_of(&stats, 2).sanctioned_resources["fish"], Fixed::from_int(2));
+    }
+
+    #[test]
+    fn self_sanctions_are_ignored() {
+        let stats = apply_sanctions(PARAMS, holding(10), &[sanction(1, 1, 5, SanctionKind::Reputation)]);
+        assert_eq!(stats, holding(10));
+    }
+
+    #[test]
+    fn sanctions_survive_into_the_next_round() {
+        let params = GameParams {
+            sanctions: Some(PARAMS),
+            ..GameParams::single_pool("fish", Fixed::from_int(100), RegenerationModel::Exponential { rate: Fixed::ZERO })
+        };
+        let sanctioned = apply_sanctions(
+            PARAMS,
+            holding(10),
+            &[sanction(1, 2, 2, SanctionKind::Resources), sanction(2, 1, 1, SanctionKind::Reputation)],
+        );
+        let moves: Vec<GameMove> = (1..=2)
+            .map(|seat| GameMove {
+                owner: agent(seat),
+                round: EntryHash::from_raw_36(vec![1; 36]),
+                resources: vec![("fish".to_string(), Fixed::from_int(5))].into_iter().collect(),
+                quota_header_hash: None,
+            })
+            .collect();
+        let (_, stats) = calculate_round_state(&params, &params.start_resources(), &sanctioned, moves, None);
+
+        let first = stat_of(&stats, 1);
+        assert_eq!(first.resources["fish"], Fixed::from_int(13));
+        assert_eq!(first.reputation, -1);
+        let second = stat_of(&stats, 2);
+        assert_eq!(second.resources["fish"], Fixed::from_int(8));
+        assert_eq!(second.sanctioned_resources["fish"], Fixed::from_int(6));
+    }
+}
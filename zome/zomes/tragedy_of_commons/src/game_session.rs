@@ -1,14 +1,28 @@
-use crate::types::{PlayerStats, ResourceAmount, new_player_stats};
+use crate::fixed_point::Fixed;
+use crate::game_sanction::SanctionParams;
+use crate::quota_vote::QuotaRule;
+use crate::chat::SignalPayloadChatMessage;
+use crate::bots::{run_bot_seats, BotSeat};
+use crate::game_preset::GamePreset;
+use crate::reputation::{career_scores, starting_stats};
+use crate::spectators::signal_recipients;
+use crate::properties::{am_i_researcher, DnaProperties};
+use crate::session_control::SignalPayloadSessionStatus;
+use crate::tournament::{validate_tournament_table, TournamentTable};
+use crate::regeneration::{RegenerationModel, ShockParams};
+use crate::types::{PlayerStat, PlayerStats, ResourceAmount, ResourcePools, new_player_stats};
 use crate::{
-    game_round::{GameRound, RoundState},
+    game_round::{ClosingPolicy, GameRound, RoundState},
     types::ReputationAmount,
-    utils::convert_keys_from_b64,
+    utils::{convert_keys_from_b64, entry_hash_from_element, now, try_from_element, try_get_latest_element},
 };
 use hdk::prelude::*;
 use holo_hash::*;
 use std::{collections::HashMap, time::SystemTime};
 use uuid::Uuid;
 
+const SESSIONS_PATH: &str = "sessions";
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum SessionState {
     InProgress,
@@ -18,24 +32,121 @@ pub enum SessionState {
     Finished,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Copy, PartialEq, Eq)]
-pub struct GameParams {
-    pub regeneration_factor: u32,
+/// A named pool of resources, e.g. fish or forest, with its own dynamics
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ResourceParams {
+    pub name: String,
     pub start_amount: ResourceAmount,
+    pub regeneration: RegenerationModel,
+}
+
+/// Factors and coefficients are Fixed so every agent calculates rounds the same way,
+/// see fixed_point.rs for the rounding rules.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GameParams {
+    pub resources: Vec<ResourceParams>,
     pub num_rounds: u32,
-    pub resource_coef: u32,
-    pub reputation_coef: u32,
+    pub resource_coef: Fixed,
+    pub reputation_coef: Fixed,
+    /// random shocks to the regrowth of all pools, None means resources regrow the same every round
+    pub shocks: Option<ShockParams>,
+    /// adds a sanction phase to every round, None means rounds finish right after the moves
+    pub sanctions: Option<SanctionParams>,
+    /// players vote on a per-player cap before the moves of every round,
+    /// None means there is no cap
+    pub quota_voting: Option<QuotaRule>,
+    /// whether players can talk to each other with chat messages during the session
+    pub chat_enabled: bool,
+    /// players start the first round with the reputation they earned in earlier sessions
+    /// instead of none, see reputation.rs
+    pub inherit_reputation: bool,
+    /// who can close the rounds, see ClosingPolicy
+    pub closing_policy: ClosingPolicy,
+}
+
+impl GameParams {
+    /// Params with one resource pool and nothing else switched on: three rounds, plain
+    /// score coefficients, and anyone can close the rounds. Set the rest with struct update syntax.
+    pub fn single_pool(name: &str, start_amount: ResourceAmount, regeneration: RegenerationModel) -> GameParams {
+        GameParams {
+            resources: vec![ResourceParams {
+                name: name.into(),
+                start_amount,
+                regeneration,
+            }],
+            num_rounds: 3,
+            resource_coef: Fixed::ONE,
+            reputation_coef: Fixed::ONE,
+            shocks: None,
+            sanctions: None,
+            quota_voting: None,
+            chat_enabled: false,
+            inherit_reputation: false,
+            closing_policy: ClosingPolicy::Anyone,
+        }
+    }
+
+    pub fn resource_names(&self) -> Vec<String> {
+        self.resources.iter().map(|r| r.name.clone()).collect()
+    }
+
+    /// Amounts of every resource pool at the start of the game
+    pub fn start_resources(&self) -> ResourcePools {
+        self.resources
+            .iter()
+            .map(|r| (r.name.clone(), r.start_amount))
+            .collect()
+    }
+
+    /// Final score of a player: all resources they hold, weighted by resource_coef,
+    /// plus their reputation weighted by reputation_coef
+    pub fn score(&self, stat: &PlayerStat) -> Fixed {
+        let resources: Fixed = stat.resources.values().sum();
+        resources * self.resource_coef + Fixed::from_int(stat.reputation as i64) * self.reputation_coef
+    }
+
+    /// Share of the start resources of all pools that is left at the end of the game.
+    /// 1 means the commons is as big as at the start, it can be more when it grew.
+    pub fn sustainability(&self, resources_left: &ResourcePools) -> Fixed {
+        let at_start: Fixed = self.resources.iter().map(|r| r.start_amount).sum();
+        let left: Fixed = resources_left.values().map(|amount| std::cmp::max(*amount, Fixed::ZERO)).sum();
+        left.checked_div(at_start).unwrap_or(Fixed::ZERO)
+    }
+
+    /// Checks that the params make sense, used when validating sessions and presets
+    pub fn validate(&self) -> Result<(), String> {
+        if self.resources.is_empty() {
+            return Err("A session needs at least one resource pool".into());
+        }
+        for (i, resource) in self.resources.iter().enumerate() {
+            if self.resources[..i].iter().any(|r| r.name == resource.name) {
+                return Err("Resource pool names have to be unique".into());
+            }
+            resource.regeneration.validate()?;
+        }
+        if let Some(shocks) = self.shocks {
+            shocks.validate()?;
+        }
+        Ok(())
+    }
 }
 
 #[hdk_entry(id = "game_session", visibility = "public")]
 #[derive(Clone, PartialEq, Eq)]
 pub struct GameSession {
     pub owner: AgentPubKeyB64, // who started the game
-    // pub created_at: Timestamp,     // when the game was started
+    pub created_at: Timestamp,     // when the game was started, keeps sessions with the same players and params apart
 //    pub status: SessionState,         // how the game is going   ---> TIXEL: why make it stateful, creates the need for updates, 
                                       // increases the eventual consistency challenges
     pub game_params: GameParams,      // what specific game are we playing
     pub players: Vec<AgentPubKeyB64>, // who is playing
+    pub bots: Vec<BotSeat>,           // which of the players are bots
+    pub preset: Option<EntryHashB64>, // preset the params were copied from, if any
+    pub previous_session: Option<HeaderHashB64>, // session this one is a rematch of, if any
+    pub tournament: Option<TournamentTable>, // where the session sits in a tournament, if any
+    pub spectators: Vec<AgentPubKeyB64>, // who is watching without playing
+    pub public: bool,                 // whether anybody can join as spectator
+    pub inherited_scores: Vec<EntryHashB64>, // GameScores the starting reputation is summed from, see starting_stats
 }
 
 #[hdk_entry(id = "game_scores", visibility = "public")]
@@ -44,12 +155,28 @@ pub struct GameScores {
     // pub ended_at: Timestamp,     // when the game was started
     pub session: EntryHashB64,      // which game
     pub stats: PlayerStats,         // who is playing
+    pub last_round: HeaderHashB64,  // finished version of the last round, the scores are taken from it
+    pub sustainability: Fixed,      // see GameParams::sustainability
+}
+
+/// Where the params of a new session come from
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum SessionParams {
+    Custom(GameParams),
+    /// entry hash of a GamePreset, the session keeps a copy of the params
+    /// of its latest version, so later edits of the preset don't affect it
+    Preset(EntryHashB64),
+    /// default params of the deployment, see default_params
+    Default,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
 pub struct GameSessionInput {
-    pub game_params: GameParams,
+    pub game_params: SessionParams,
     pub players: Vec<AgentPubKeyB64>,
+    pub bots: Vec<BotSeat>,
+    pub spectators: Vec<AgentPubKeyB64>,
+    pub public: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, SerializedBytes)]
@@ -57,6 +184,7 @@ pub struct SignalPayloadNextRound {
     pub game_session_header_hash: HeaderHashB64,
     pub current_round_header_hash: HeaderHashB64,
     pub next_round_header_hash: HeaderHashB64,
+    pub next_round: GameRound,
 }
 
 #[derive(Debug, Serialize, Deserialize, SerializedBytes)]
@@ -67,6 +195,21 @@ pub struct SignalPayloadStartGame {
     pub current_round_header_hash: HeaderHashB64,
 }
 
+#[derive(Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct SignalPayloadStartSanctions {
+    pub game_session_header_hash: HeaderHashB64,
+    pub current_round_header_hash: HeaderHashB64,
+    // round with the results of the moves, before sanctions
+    pub current_round: GameRound,
+}
+
+#[derive(Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct SignalPayloadQuotaDecided {
+    pub game_session_header_hash: HeaderHashB64,
+    pub current_round_header_hash: HeaderHashB64,
+    pub quota: ResourcePools,
+}
+
 #[derive(Debug, Serialize, Deserialize, SerializedBytes)]
 pub struct SignalPayloadGameOver {
     pub game_scores: GameScores,
@@ -99,18 +242,29 @@ impl GameSession {
 // placeholder function that can be called from UI/test, until invitation zoom is added.
 pub fn start_dummy_session(player_list: Vec<AgentPubKeyB64>) -> ExternResult<HeaderHash> {
     let input = GameSessionInput {
-        game_params: GameParams {
-            regeneration_factor: 1,
-            start_amount: 100,
-            num_rounds: 3,
-            resource_coef: 3,
-            reputation_coef: 2,
-        },
+        game_params: SessionParams::Default,
         players: player_list,
+        bots: vec![],
+        spectators: vec![],
+        public: false,
     };
     new_session(input)
 }
 
+/// Params set as default_params in the DNA properties, or a single pool of 100
+/// that doesn't regrow for three rounds if the deployment doesn't set any
+pub fn default_params() -> ExternResult<GameParams> {
+    match DnaProperties::get()?.default_params {
+        Some(params) => Ok(params),
+        None => Ok(GameParams {
+            resource_coef: Fixed::from_int(3),
+            reputation_coef: Fixed::from_int(2),
+            chat_enabled: true,
+            ..GameParams::single_pool("commons", Fixed::from_int(100), RegenerationModel::Exponential { rate: Fixed::ZERO })
+        }),
+    }
+}
+
 /// Create a new GameSession with the confirmed players (who accepted their invites).
 /// NOTE: we're only creating session for those who accepted and only if there are at
 /// least two of them -- otherwise there won't be any turns.
@@ -118,14 +272,109 @@ pub fn new_session(input: GameSessionInput) -> ExternResult<HeaderHash> {
     // agent that starts new game
     let agent_info: AgentInfo = agent_info()?;
 
-    // TODO: get timestamp as systime
-    
+    let (game_params, preset) = resolve_params(input.game_params)?;
+
     // create entry for game session
     let gs = GameSession {
         owner: AgentPubKeyB64::from(agent_info.agent_initial_pubkey),
-        game_params: input.game_params,  // rules of the game
+        created_at: now()?,
+        game_params,  // rules of the game
         players: input.players.clone(),
+        bots: input.bots.clone(),
+        preset,
+        previous_session: None,
+        tournament: None,
+        spectators: input.spectators.clone(),
+        public: input.public,
+        inherited_scores: vec![],
     };
+    Ok(start_session(gs)?.0)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct SessionListing {
+    pub session_header_hash: HeaderHashB64,
+    pub session: GameSession,
+}
+
+/// Lists every session of the deployment, only the researcher can do this
+#[hdk_extern]
+pub fn get_all_sessions(_: ()) -> ExternResult<Vec<SessionListing>> {
+    if !am_i_researcher()? {
+        return Err(WasmError::Guest("Only the researcher can list all sessions".into()));
+    }
+    let links = get_links(Path::from(SESSIONS_PATH).hash()?, Some(LinkTag::new("game_session")))?;
+    let mut sessions: Vec<SessionListing> = vec![];
+    for link in links.into_inner() {
+        if let Some(element) = get(link.target, GetOptions::default())? {
+            sessions.push(SessionListing {
+                session_header_hash: element.header_address().clone().into(),
+                session: try_from_element(element)?,
+            });
+        }
+    }
+    Ok(sessions)
+}
+
+/// Returns the params a session is started with, and the preset they were copied from
+pub fn resolve_params(params: SessionParams) -> ExternResult<(GameParams, Option<EntryHashB64>)> {
+    match params {
+        SessionParams::Custom(params) => Ok((params, None)),
+        SessionParams::Preset(preset_hash) => {
+            let preset: GamePreset = try_from_element(try_get_latest_element(preset_hash.clone().into())?)?;
+            Ok((preset.game_params, Some(preset_hash)))
+        }
+        SessionParams::Default => Ok((default_params()?, None)),
+    }
+}
+
+/// Starts a new session with the same players, bots and params as a finished one.
+/// Any player of the finished session can call it, but every session only gets one rematch.
+#[hdk_extern]
+pub fn rematch(previous_session_header_hash: HeaderHashB64) -> ExternResult<HeaderHash> {
+    let previous_element = match get(HeaderHash::from(previous_session_header_hash.clone()), GetOptions::content())? {
+        Some(element) => element,
+        None => return Err(WasmError::Guest("Session not found".into())),
+    };
+    let previous_session_entry_hash = entry_hash_from_element(previous_element.clone())?;
+    let previous_session: GameSession = try_from_element(previous_element)?;
+
+    let me = AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey);
+    if !previous_session.players.contains(&me) {
+        return Err(WasmError::Guest("Only players of a session can start a rematch".into()));
+    }
+    if get_links(previous_session_entry_hash.clone(), Some(LinkTag::new("game_scores")))?.into_inner().is_empty() {
+        return Err(WasmError::Guest("Cannot start a rematch before the game is over".into()));
+    }
+    if !get_links(previous_session_entry_hash.clone(), Some(LinkTag::new("rematch")))?.into_inner().is_empty() {
+        return Err(WasmError::Guest("This session already has a rematch".into()));
+    }
+
+    let gs = GameSession {
+        owner: me,
+        created_at: now()?,
+        previous_session: Some(previous_session_header_hash),
+        tournament: None,
+        ..previous_session
+    };
+    let (round_one_header_hash, game_session_entry_hash) = start_session(gs)?;
+    create_link(previous_session_entry_hash, game_session_entry_hash, LinkTag::new("rematch"))?;
+    Ok(round_one_header_hash)
+}
+
+/// Creates the session entry and its first round, and lets all players know the game started.
+/// Fails if the session is outside the limits set in the DNA properties.
+/// Returns the header hash of the first round and the entry hash of the session.
+pub fn start_session(mut gs: GameSession) -> ExternResult<(HeaderHash, EntryHash)> {
+    if let Err(reason) = DnaProperties::get()?.limits.check(&gs) {
+        return Err(WasmError::Guest(reason));
+    }
+    let inherited = match gs.game_params.inherit_reputation {
+        true => career_scores(&gs.players)?,
+        false => vec![],
+    };
+    gs.inherited_scores = inherited.iter().map(|(entry_hash, _)| entry_hash.clone().into()).collect();
+    let inherited: Vec<GameScores> = inherited.into_iter().map(|(_, game_scores)| game_scores).collect();
     let game_session_header_hash = create_entry(&gs)?;
     let game_session_entry_hash = hash_entry(&gs)?;
 
@@ -139,19 +388,21 @@ pub fn new_session(input: GameSessionInput) -> ExternResult<HeaderHash> {
 
     // create game round results for round 0
     // this is starting point for all the game moves of round 1 to reference (implicit link)
-    let no_moves: Vec<EntryHash> = vec![];
-
-    // TODO: create a link from session to game round entry to make the round discoverable
-    let round_one = GameRound {
-        round_state: RoundState::InProgress,
-        round_num: 1,
-        session_header_hash: game_session_header_hash.clone().into(),
-        player_stats: new_player_stats(input.players.clone()),
-        player_moves: no_moves,
-        resources_left: gs.game_params.start_amount,
-    };
+    let round_one = GameRound::start(
+        &gs.game_params,
+        1,
+        game_session_header_hash.clone().into(),
+        gs.game_params.start_resources(),
+        starting_stats(&gs, &inherited),
+    );
 
     let round_one_header_hash = create_entry(&round_one)?;
+    // index all sessions for the researcher, see get_all_sessions
+    let path = Path::from(SESSIONS_PATH);
+    path.ensure()?;
+    create_link(path.hash()?, game_session_entry_hash.clone(), LinkTag::new("game_session"))?;
+    // link the round from the session to make it discoverable, e.g. for exports
+    create_link(game_session_entry_hash.clone(), hash_entry(&round_one)?, LinkTag::new("game_round"))?;
 
     // use remote signals from RSM to send a real-time notif to invited players
     //  ! using remote signal to ping other holochain backends, instead of emit_signal
@@ -168,13 +419,107 @@ pub fn new_session(input: GameSessionInput) -> ExternResult<HeaderHash> {
     let signal = ExternIO::encode(GameSignal::StartGame(signal_payload))?;
     // Since we're storing agent keys as AgentPubKeyB64, and remote_signal only accepts
     // the AgentPubKey type, we need to convert our keys to the expected data type
-    remote_signal(signal, convert_keys_from_b64(input.players.clone()))?;
-    tracing::debug!("sending signal to {:?}", input.players.clone());
+    remote_signal(signal, signal_recipients(&gs)?)?;
+    tracing::debug!("sending signal to {:?}", gs.players.clone());
+    // the owner plays the bot seats of the first round
+    run_bot_seats(round_one_header_hash.clone())?;
 
     // // todo: get timestamp as systime
     // create_entry(&calendar_event)?;
 
-    Ok(round_one_header_hash)
+    Ok((round_one_header_hash, game_session_entry_hash))
+}
+
+#[hdk_extern]
+pub fn validate_create_entry_game_session(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let game_session: GameSession = try_from_element(data.element.clone())?;
+    // the Owner closing policy and the right to pause or abort the session rely on this
+    if AgentPubKey::from(game_session.owner.clone()) != *data.element.header().author() {
+        return Ok(ValidateCallbackResult::Invalid("A session can only be created by its owner".into()));
+    }
+    if let Some(previous_session_header_hash) = game_session.previous_session.clone() {
+        let previous_session_header_hash: HeaderHash = previous_session_header_hash.into();
+        let previous_session: GameSession = match get(previous_session_header_hash.clone(), GetOptions::content())? {
+            Some(element) => try_from_element(element)?,
+            None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![previous_session_header_hash.into()])),
+        };
+        let author = AgentPubKeyB64::from(data.element.header().author().clone());
+        if game_session.owner != author || !previous_session.players.contains(&author) {
+            return Ok(ValidateCallbackResult::Invalid("Only players of a session can start a rematch".into()));
+        }
+        if game_session.players != previous_session.players
+            || game_session.bots != previous_session.bots
+            || game_session.game_params != previous_session.game_params
+        {
+            return Ok(ValidateCallbackResult::Invalid("A rematch has to keep the players and params of its session".into()));
+        }
+    }
+    if let Some(table) = game_session.tournament.clone() {
+        match validate_tournament_table(&game_session, &table, data.element.header().author())? {
+            ValidateCallbackResult::Valid => {}
+            result => return Ok(result),
+        }
+    }
+    if let Err(reason) = game_session.game_params.validate() {
+        return Ok(ValidateCallbackResult::Invalid(reason));
+    }
+    if !game_session.game_params.inherit_reputation && !game_session.inherited_scores.is_empty() {
+        return Ok(ValidateCallbackResult::Invalid("Only sessions that inherit reputation can list scores".into()));
+    }
+    if let Err(reason) = DnaProperties::get()?.limits.check(&game_session) {
+        return Ok(ValidateCallbackResult::Invalid(reason));
+    }
+    for (i, bot) in game_session.bots.iter().enumerate() {
+        if !game_session.players.contains(&bot.agent) {
+            return Ok(ValidateCallbackResult::Invalid("A bot has to sit on a player seat".into()));
+        }
+        if game_session.bots[..i].iter().any(|b| b.agent == bot.agent) {
+            return Ok(ValidateCallbackResult::Invalid("A seat can only have one bot".into()));
+        }
+    }
+    if game_session.spectators.iter().any(|s| game_session.players.contains(s)) {
+        return Ok(ValidateCallbackResult::Invalid("A player can't be a spectator of their own session".into()));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
+/// Scores can only be written for a game that is over, by an agent the closing policy allows
+/// to close its last round, and have to match that round.
+/// Career stats and leaderboards rely on this, see leaderboard.rs.
+#[hdk_extern]
+pub fn validate_create_entry_game_scores(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let game_scores: GameScores = try_from_element(data.element.clone())?;
+    let last_round_header_hash: HeaderHash = game_scores.last_round.clone().into();
+    let last_round: GameRound = match get(last_round_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![last_round_header_hash.into()])),
+    };
+    let session_header_hash: HeaderHash = last_round.session_header_hash.clone().into();
+    let game_session: GameSession = match get(session_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![session_header_hash.into()])),
+    };
+
+    if EntryHashB64::from(hash_entry(&game_session)?) != game_scores.session {
+        return Ok(ValidateCallbackResult::Invalid("Scores have to belong to the session of their last round".into()));
+    }
+    // the scores are written together with the last version of the last round
+    let author = AgentPubKeyB64::from(data.element.header().author().clone());
+    if !game_session.game_params.closing_policy.may_close(&game_session, last_round.round_num, &author) {
+        return Ok(ValidateCallbackResult::Invalid("Only agents that may close the last round can write the scores".into()));
+    }
+    let all_pools_left = last_round.resources_left.values().all(|amount| amount.is_positive());
+    if last_round.round_state != RoundState::Finished
+        || (last_round.round_num < game_session.game_params.num_rounds && all_pools_left)
+    {
+        return Ok(ValidateCallbackResult::Invalid("Scores can only be written once the game is over".into()));
+    }
+    if game_scores.stats != last_round.player_stats
+        || game_scores.sustainability != game_session.game_params.sustainability(&last_round.resources_left)
+    {
+        return Ok(ValidateCallbackResult::Invalid("Scores have to match the last round".into()));
+    }
+    Ok(ValidateCallbackResult::Valid)
 }
 
 #[derive(Serialize, Deserialize, SerializedBytes, Debug)]
@@ -182,7 +527,11 @@ pub fn new_session(input: GameSessionInput) -> ExternResult<HeaderHash> {
 pub enum GameSignal {
     StartGame(SignalPayloadStartGame),
     NextRound(SignalPayloadNextRound),
+    StartSanctions(SignalPayloadStartSanctions),
+    QuotaDecided(SignalPayloadQuotaDecided),
+    ChatMessage(SignalPayloadChatMessage),
     GameOver(SignalPayloadGameOver),
+    SessionStatusChanged(SignalPayloadSessionStatus),
 }
 
 // #[cfg(test)]
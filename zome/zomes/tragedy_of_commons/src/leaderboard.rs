@@ -0,0 +1,182 @@
+use crate::{
+    fixed_point::Fixed,
+    game_session::{GameScores, GameSession},
+    types::ReputationAmount,
+    utils::{try_from_element, try_get_and_convert},
+};
+use chrono::NaiveDateTime;
+use hdk::prelude::*;
+use holo_hash::*;
+use std::time::Duration;
+
+const SCORES_PATH: &str = "scores";
+
+/// How a player did in one finished session
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct PlayerResult {
+    pub session: EntryHashB64,
+    pub score: Fixed,
+    pub reputation: ReputationAmount,
+    pub sustainability: Fixed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct CareerStats {
+    pub agent: AgentPubKeyB64,
+    pub games_played: u32,
+    pub average_score: Fixed,
+    pub average_reputation: Fixed,
+    /// average sustainability of the sessions the player played in
+    pub average_sustainability: Fixed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct LeaderboardInput {
+    /// month in the form "2021-06", None for the all time leaderboard
+    pub bucket: Option<String>,
+    pub top_n: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct LeaderboardEntry {
+    pub agent: AgentPubKeyB64,
+    pub games_played: u32,
+    pub total_score: Fixed,
+    pub average_sustainability: Fixed,
+}
+
+/// Links new scores from every player that has stats in them, and from the path of
+/// the month the game ended in, e.g. "scores.2021-06"
+pub fn index_scores(game_scores: &GameScores, game_scores_entry_hash: EntryHash) -> ExternResult<()> {
+    for agent in game_scores.stats.keys() {
+        create_link(
+            AgentPubKey::from(agent.clone()).into(),
+            game_scores_entry_hash.clone(),
+            LinkTag::new("player_scores"),
+        )?;
+    }
+    let path = Path::from(format!("{}.{}", SCORES_PATH, time_bucket(sys_time()?)));
+    path.ensure()?;
+    create_link(path.hash()?, game_scores_entry_hash, LinkTag::new("game_scores"))?;
+    Ok(())
+}
+
+/// Results of all finished sessions the agent played in
+#[hdk_extern]
+pub fn get_player_history(agent: AgentPubKeyB64) -> ExternResult<Vec<PlayerResult>> {
+    let links = get_links(AgentPubKey::from(agent.clone()).into(), Some(LinkTag::new("player_scores")))?;
+    let mut history: Vec<PlayerResult> = vec![];
+    for (game_scores, game_session) in get_scores(links)? {
+        if let Some(result) = player_result(&agent, &game_scores, &game_session) {
+            history.push(result);
+        }
+    }
+    Ok(history)
+}
+
+#[hdk_extern]
+pub fn get_career_stats(agent: AgentPubKeyB64) -> ExternResult<CareerStats> {
+    let history = get_player_history(agent.clone())?;
+    let games = Fixed::from_int(history.len() as i64);
+    let average = |total: Fixed| total.checked_div(games).unwrap_or(Fixed::ZERO);
+    Ok(CareerStats {
+        agent,
+        games_played: history.len() as u32,
+        average_score: average(history.iter().map(|r| r.score).sum()),
+        average_reputation: average(history.iter().map(|r| Fixed::from_int(r.reputation as i64)).sum()),
+        average_sustainability: average(history.iter().map(|r| r.sustainability).sum()),
+    })
+}
+
+/// The top_n players by total score, of one month or of all time
+#[hdk_extern]
+pub fn get_leaderboard(input: LeaderboardInput) -> ExternResult<Vec<LeaderboardEntry>> {
+    let buckets: Vec<EntryHash> = match input.bucket {
+        Some(bucket) => vec![Path::from(format!("{}.{}", SCORES_PATH, bucket)).hash()?],
+        None => Path::from(SCORES_PATH)
+            .children()?
+            .into_inner()
+            .into_iter()
+            .map(|link| link.target)
+            .collect(),
+    };
+
+    let mut entries: Vec<LeaderboardEntry> = vec![];
+    let mut sustainability: Vec<Fixed> = vec![];
+    for bucket in buckets {
+        let links = get_links(bucket, Some(LinkTag::new("game_scores")))?;
+        for (game_scores, game_session) in get_scores(links)? {
+            for agent in game_scores.stats.keys() {
+                let result = match player_result(agent, &game_scores, &game_session) {
+                    Some(result) => result,
+                    None => continue,
+                };
+                let i = match entries.iter().position(|e| &e.agent == agent) {
+                    Some(i) => i,
+                    None => {
+                        entries.push(LeaderboardEntry {
+                            agent: agent.clone(),
+                            games_played: 0,
+                            total_score: Fixed::ZERO,
+                            average_sustainability: Fixed::ZERO,
+                        });
+                        sustainability.push(Fixed::ZERO);
+                        entries.len() - 1
+                    }
+                };
+                entries[i].games_played += 1;
+                entries[i].total_score += result.score;
+                sustainability[i] += result.sustainability;
+            }
+        }
+    }
+    for (entry, total) in entries.iter_mut().zip(sustainability) {
+        entry.average_sustainability = total
+            .checked_div(Fixed::from_int(entry.games_played as i64))
+            .unwrap_or(Fixed::ZERO);
+    }
+    entries.sort_by(|a, b| b.total_score.cmp(&a.total_score));
+    entries.truncate(input.top_n as usize);
+    Ok(entries)
+}
+
+// NOTE: get only returns entries that passed validation. Scores have to match the last round
+// of their session, and every round is checked against the moves it lists and against the
+// round before it, back to the start of the session. So the scores come from moves that were
+// really made, but nothing stops one person from playing a session with several agents.
+/// Scores the links point to, with their sessions. Scores linked twice are only returned once.
+fn get_scores(links: Links) -> ExternResult<Vec<(GameScores, GameSession)>> {
+    let mut scores: Vec<(GameScores, GameSession)> = vec![];
+    for link in links.into_inner() {
+        let game_scores: GameScores = match get(link.target, GetOptions::default())? {
+            Some(element) => match try_from_element(element) {
+                Ok(game_scores) => game_scores,
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        if scores.iter().any(|(s, _)| s.session == game_scores.session) {
+            continue;
+        }
+        let game_session: GameSession = try_get_and_convert(game_scores.session.clone().into())?;
+        scores.push((game_scores, game_session));
+    }
+    Ok(scores)
+}
+
+fn player_result(agent: &AgentPubKeyB64, game_scores: &GameScores, game_session: &GameSession) -> Option<PlayerResult> {
+    let stat = game_scores.stats.get(agent)?;
+    Some(PlayerResult {
+        session: game_scores.session.clone(),
+        score: game_session.game_params.score(stat),
+        reputation: stat.reputation,
+        sustainability: game_scores.sustainability,
+    })
+}
+
+/// Month of a point in time, e.g. "2021-06"
+fn time_bucket(time: Duration) -> String {
+    NaiveDateTime::from_timestamp(time.as_secs() as i64, 0)
+        .format("%Y-%m")
+        .to_string()
+}
@@ -8,19 +8,83 @@ use crate::{
     game_move::GameMoveInput,
     game_session::{GameSessionInput, GameSignal},
 };
+// NOTE: the rules of the game are plain functions next to the entries they are about, like
+// calculate_round_state, apply_sanctions, decide_quota, ClosingPolicy::may_close or bot_plan.
+// They are used both when writing entries and when validating them, so they can't make any
+// DHT queries, which also lets metrics and tests run them outside of a conductor.
 #[allow(unused_imports)]
 #[allow(dead_code)]
 #[allow(unused)]
-mod game_move;
+pub mod game_move;
 #[allow(unused_imports)]
 #[allow(dead_code)]
 #[allow(unused)]
-mod game_round;
+pub mod game_round;
 #[allow(unused_imports)]
 #[allow(dead_code)]
 #[allow(unused)]
-mod game_session;
-mod types;
+pub mod game_session;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+mod game_preset;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+mod tournament;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+mod leaderboard;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+mod reputation;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+mod spectators;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+pub mod export;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+pub mod metrics;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+mod experiment;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+mod session_control;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+mod membrane;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+pub mod game_sanction;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+pub mod quota_vote;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+mod chat;
+#[allow(unused_imports)]
+#[allow(dead_code)]
+#[allow(unused)]
+pub mod bots;
+pub mod fixed_point;
+mod properties;
+pub mod random;
+pub mod regeneration;
+pub mod types;
 mod utils;
 
 pub fn err(reason: &str) -> WasmError {
@@ -30,8 +94,16 @@ pub fn err(reason: &str) -> WasmError {
 entry_defs![
     Path::entry_def(),
     game_session::GameSession::entry_def(),
+    game_preset::GamePreset::entry_def(),
+    tournament::Tournament::entry_def(),
+    experiment::Experiment::entry_def(),
+    experiment::ExperimentAssignment::entry_def(),
+    session_control::SessionControl::entry_def(),
     game_round::GameRound::entry_def(),
     game_move::GameMove::entry_def(),
+    game_sanction::GameSanction::entry_def(),
+    quota_vote::QuotaVote::entry_def(),
+    chat::ChatMessage::entry_def(),
     game_session::GameScores::entry_def()
 ];
 
@@ -63,6 +135,25 @@ fn recv_remote_signal(signal: ExternIO) -> ExternResult<()> {
     }
 }
 
+// links have no types, so the rules of a link are picked by its tag
+#[hdk_extern]
+fn validate_create_link(data: ValidateCreateLinkData) -> ExternResult<ValidateLinkCallbackResult> {
+    if data.link_add.tag == LinkTag::new("spectator") {
+        return spectators::validate_create_link_spectator(data);
+    }
+    Ok(ValidateLinkCallbackResult::Valid)
+}
+
+#[hdk_extern]
+pub fn get_dna_props(_: ()) -> ExternResult<properties::DnaProperties> {
+    properties::DnaProperties::get()
+}
+
+#[hdk_extern]
+pub fn am_i_researcher(_: ()) -> ExternResult<bool> {
+    properties::am_i_researcher()
+}
+
 /// Placeholder function that can be called from UI/test, until invitation zoom is added.
 #[hdk_extern]
 pub fn start_dummy_session(player_list: Vec<AgentPubKeyB64>) -> ExternResult<HeaderHash> {
@@ -110,6 +201,13 @@ pub fn try_to_close_round(round_hash: HeaderHashB64) -> ExternResult<HeaderHashB
     game_round::try_to_close_round(round_hash.into())
 }
 
+/// Function for the owner of a session to play its bot seats in a phase of the round
+/// that was started by a player who doesn't play them, see bots::may_play_bots
+#[hdk_extern]
+pub fn play_bots(round_hash: HeaderHashB64) -> ExternResult<()> {
+    bots::play_bots(round_hash.into())
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
 pub struct SignalTest {
     pub content: String,
@@ -0,0 +1,107 @@
+use crate::properties::DnaProperties;
+use hdk::prelude::*;
+use holo_hash::*;
+
+/// Membrane proof of an enrolled participant: the progenitor or the researcher signed
+/// the agent key of the participant together with the DNA of the deployment, so a token
+/// can't be reused by another agent or in another deployment.
+/// It's passed msgpack encoded as the membrane proof when the app is installed.
+#[derive(Serialize, Deserialize, SerializedBytes, Debug, Clone)]
+pub struct InviteToken {
+    pub agent: AgentPubKeyB64,
+    pub signer: AgentPubKeyB64,
+    pub signature: Signature,
+}
+
+/// What the signature of an InviteToken is made over
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct InvitePayload {
+    agent: AgentPubKeyB64,
+    dna_hash: DnaHashB64,
+}
+
+/// Signs an invite for an agent to join the network, only the progenitor and the
+/// researcher can do this. The agent key has to be known before the agent installs the app.
+#[hdk_extern]
+pub fn create_invite(agent: AgentPubKeyB64) -> ExternResult<InviteToken> {
+    let me = agent_info()?.agent_initial_pubkey;
+    if !invite_signers(&DnaProperties::get()?).contains(&me) {
+        return Err(WasmError::Guest("Only the progenitor or the researcher can invite participants".into()));
+    }
+    let payload = InvitePayload {
+        agent: agent.clone(),
+        dna_hash: zome_info()?.dna_hash.into(),
+    };
+    Ok(InviteToken {
+        agent,
+        signer: me.clone().into(),
+        signature: sign(me, payload)?,
+    })
+}
+
+/// Agents that can sign invites
+fn invite_signers(props: &DnaProperties) -> Vec<AgentPubKey> {
+    props
+        .progenitor
+        .iter()
+        .chain(props.researcher_address.iter())
+        .map(|agent| AgentPubKey::from(agent.clone()))
+        .collect()
+}
+
+// NOTE: the hdk version this zome is built with has no genesis_self_check callback,
+// so an agent without a valid invite can still install the app. Its agent key is rejected
+// here as soon as it's published, which keeps it out of the network all the same.
+// Once the hdk is upgraded, genesis_self_check should run validate_invite as well, so the
+// install itself fails early.
+/// When the DNA properties require invites, only the progenitor, the researcher and agents
+/// with a valid InviteToken as membrane proof can join. The membrane proof is in the
+/// AgentValidationPkg header, which is written right before the agent key.
+#[hdk_extern]
+pub fn validate_create_agent(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let props = DnaProperties::get()?;
+    if !props.require_invite {
+        return Ok(ValidateCallbackResult::Valid);
+    }
+    let agent = data.element.header().author().clone();
+    let signers = invite_signers(&props);
+    if signers.contains(&agent) {
+        return Ok(ValidateCallbackResult::Valid);
+    }
+
+    let prev_header = match data.element.header().prev_header() {
+        Some(prev_header) => prev_header.clone(),
+        None => return Ok(ValidateCallbackResult::Invalid("Agent key has to follow the membrane proof".into())),
+    };
+    let membrane_proof = match get(prev_header.clone(), GetOptions::content())? {
+        Some(element) => match element.header() {
+            Header::AgentValidationPkg(pkg) => pkg.membrane_proof.clone(),
+            _ => return Ok(ValidateCallbackResult::Invalid("Agent key has to follow the membrane proof".into())),
+        },
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![prev_header.into()])),
+    };
+    let token = match membrane_proof.map(InviteToken::try_from) {
+        Some(Ok(token)) => token,
+        Some(Err(_)) => return Ok(ValidateCallbackResult::Invalid("Membrane proof isn't an invite token".into())),
+        None => return Ok(ValidateCallbackResult::Invalid("Joining this network needs an invite".into())),
+    };
+    validate_invite(&token, &agent, &signers)
+}
+
+fn validate_invite(token: &InviteToken, agent: &AgentPubKey, signers: &[AgentPubKey]) -> ExternResult<ValidateCallbackResult> {
+    if &AgentPubKey::from(token.agent.clone()) != agent {
+        return Ok(ValidateCallbackResult::Invalid("Invite was made for another agent".into()));
+    }
+    let signer = AgentPubKey::from(token.signer.clone());
+    if !signers.contains(&signer) {
+        return Ok(ValidateCallbackResult::Invalid("Invite has to be signed by the progenitor or the researcher".into()));
+    }
+    let payload = InvitePayload {
+        agent: token.agent.clone(),
+        dna_hash: zome_info()?.dna_hash.into(),
+    };
+    if !verify_signature(signer, token.signature.clone(), payload)? {
+        return Ok(ValidateCallbackResult::Invalid("Invite signature is invalid".into()));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
@@ -0,0 +1,278 @@
+use crate::{
+    bots::{BotContext, BotStrategy},
+    export::{export_session, ExportInput, SessionExport},
+    fixed_point::Fixed,
+    game_round::RoundState,
+    random::SeededRng,
+    types::{PlayerStats, ResourcePools},
+};
+use hdk::prelude::*;
+use holo_hash::*;
+
+/// Analytics of a session, calculated from its rounds
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct SessionMetrics {
+    /// everything the players took from every pool, over all finished rounds
+    pub harvested: ResourcePools,
+    /// what could have been taken from every pool over all rounds of the session without
+    /// shrinking it, see RegenerationModel::max_sustainable_yield
+    pub max_sustainable_yield: ResourcePools,
+    /// harvested divided by max_sustainable_yield, above 1 means the commons was overused
+    pub harvest_ratio: ResourcePools,
+    /// inequality of the resources held in the final player stats, 0 means all equal
+    pub gini: Fixed,
+    /// finished rounds after which every pool still had resources left
+    pub rounds_survived: u32,
+    pub cooperation: Vec<PlayerCooperation>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerCooperation {
+    pub agent: AgentPubKeyB64,
+    /// share of the finished rounds in which the player took no more than their
+    /// sustainable share from any pool, between 0 and 1
+    pub index: Fixed,
+}
+
+#[hdk_extern]
+pub fn get_session_metrics(session_header_hash: HeaderHashB64) -> ExternResult<SessionMetrics> {
+    let export = export_session(ExportInput {
+        session_header_hash,
+        salt: None,
+    })?;
+    Ok(session_metrics(&export))
+}
+
+/// Metrics of an exported session, pseudonymised or not
+pub fn session_metrics(export: &SessionExport) -> SessionMetrics {
+    let params = &export.game_params;
+    let finished: Vec<_> = export
+        .rounds
+        .iter()
+        .filter(|r| r.round.round_state == RoundState::Finished)
+        .collect();
+
+    let mut harvested = ResourcePools::new();
+    let mut max_sustainable_yield = ResourcePools::new();
+    let mut harvest_ratio = ResourcePools::new();
+    for pool in params.resources.iter() {
+        let taken: Fixed = finished
+            .iter()
+            .flat_map(|r| r.moves.iter())
+            .map(|m| m.resources.get(&pool.name).copied().unwrap_or(Fixed::ZERO))
+            .sum();
+        let msy = pool.regeneration.max_sustainable_yield(pool.start_amount).mul_int(params.num_rounds as i64);
+        harvested.insert(pool.name.clone(), taken);
+        max_sustainable_yield.insert(pool.name.clone(), msy);
+        harvest_ratio.insert(pool.name.clone(), taken.checked_div(msy).unwrap_or(Fixed::ZERO));
+    }
+
+    let rounds_survived = finished
+        .iter()
+        .filter(|r| r.round.resources_left.values().all(|amount| amount.is_positive()))
+        .count() as u32;
+
+    let final_stats = match (&export.scores, finished.last()) {
+        (Some(scores), _) => scores.stats.clone(),
+        (None, Some(round)) => round.round.player_stats.clone(),
+        (None, None) => PlayerStats::new(),
+    };
+
+    // what a SustainableShare bot would have taken, given what the round started with
+    let mut within_share: Vec<(AgentPubKeyB64, i64)> =
+        export.session.players.iter().map(|p| (p.clone(), 0)).collect();
+    let start_resources = params.start_resources();
+    let no_stats = PlayerStats::new();
+    for (i, round) in finished.iter().enumerate() {
+        let at_start = match i {
+            0 => &start_resources,
+            _ => &finished[i - 1].round.resources_left,
+        };
+        let context = BotContext {
+            params,
+            round_num: round.round.round_num,
+            resources_left: at_start,
+            previous_stats: &no_stats,
+            quota: None,
+            seats: export.session.players.len(),
+        };
+        let share = BotStrategy::SustainableShare.plan_move(&context, &mut SeededRng::new(0));
+        for game_move in round.moves.iter() {
+            let cooperated = share
+                .iter()
+                .all(|(name, amount)| game_move.resources.get(name).copied().unwrap_or(Fixed::ZERO) <= *amount);
+            if let Some(entry) = within_share.iter_mut().find(|(agent, _)| agent == &game_move.author) {
+                if cooperated {
+                    entry.1 += 1;
+                }
+            }
+        }
+    }
+    let cooperation = within_share
+        .into_iter()
+        .map(|(agent, rounds)| PlayerCooperation {
+            agent,
+            index: Fixed::from_int(rounds)
+                .checked_div(Fixed::from_int(finished.len() as i64))
+                .unwrap_or(Fixed::ZERO),
+        })
+        .collect();
+
+    SessionMetrics {
+        harvested,
+        max_sustainable_yield,
+        harvest_ratio,
+        gini: gini(&final_stats),
+        rounds_survived,
+        cooperation,
+    }
+}
+
+/// Gini coefficient of the resources the players hold, summed over all pools:
+/// the mean absolute difference between any two players, divided by twice the mean
+pub fn gini(stats: &PlayerStats) -> Fixed {
+    let held: Vec<Fixed> = stats
+        .values()
+        .map(|stat| std::cmp::max(stat.resources.values().sum(), Fixed::ZERO))
+        .collect();
+    let total: Fixed = held.iter().sum();
+    let differences: Fixed = held
+        .iter()
+        .flat_map(|a| held.iter().map(move |b| if a > b { *a - *b } else { *b - *a }))
+        .sum();
+    differences
+        .checked_div(total.mul_int(2 * held.len() as i64))
+        .unwrap_or(Fixed::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{MoveExport, RoundExport};
+    use crate::game_round::GameRound;
+    use crate::game_session::{GameParams, GameSession};
+    use crate::regeneration::RegenerationModel;
+    use crate::types::{new_player_stats, PlayerStat};
+
+    fn agent(seat: u8) -> AgentPubKeyB64 {
+        AgentPubKey::from_raw_36(vec![seat; 36]).into()
+    }
+
+    fn fish(amount: i64) -> ResourcePools {
+        vec![("fish".to_string(), Fixed::from_int(amount))].into_iter().collect()
+    }
+
+    fn holding(amounts: &[i64]) -> PlayerStats {
+        amounts
+            .iter()
+            .enumerate()
+            .map(|(seat, amount)| {
+                let stat = PlayerStat {
+                    resources: fish(*amount),
+                    ..PlayerStat::default()
+                };
+                (agent(seat as u8 + 1), stat)
+            })
+            .collect()
+    }
+
+    fn params() -> GameParams {
+        GameParams {
+            num_rounds: 2,
+            ..GameParams::single_pool("fish", Fixed::from_int(100), RegenerationModel::Exponential { rate: Fixed::from_raw(100) })
+        }
+    }
+
+    fn round(round_num: u32, round_state: RoundState, left: i64, player_stats: PlayerStats, takes: &[i64]) -> RoundExport {
+        RoundExport {
+            round_header_hash: HeaderHash::from_raw_36(vec![round_num as u8; 36]).into(),
+            round: GameRound {
+                round_state,
+                round_num,
+                session_header_hash: HeaderHash::from_raw_36(vec![0; 36]).into(),
+                resources_left: fish(left),
+                player_stats,
+                player_moves: vec![],
+                player_sanctions: vec![],
+                quota: None,
+                player_votes: vec![],
+                shock: None,
+            },
+            moves: takes
+                .iter()
+                .enumerate()
+                .map(|(seat, amount)| MoveExport {
+                    move_header_hash: HeaderHash::from_raw_36(vec![seat as u8; 36]).into(),
+                    author: agent(seat as u8 + 1),
+                    timestamp: Timestamp(0, 0),
+                    resources: fish(*amount),
+                })
+                .collect(),
+        }
+    }
+
+    fn export() -> SessionExport {
+        let params = params();
+        let players = vec![agent(1), agent(2)];
+        SessionExport {
+            session_header_hash: HeaderHash::from_raw_36(vec![0; 36]).into(),
+            session: GameSession {
+                owner: agent(1),
+                created_at: Timestamp(0, 0),
+                game_params: params.clone(),
+                players: players.clone(),
+                bots: vec![],
+                preset: None,
+                previous_session: None,
+                tournament: None,
+                spectators: vec![],
+                public: false,
+                inherited_scores: vec![],
+            },
+            game_params: params.clone(),
+            rounds: vec![
+                round(1, RoundState::Finished, 88, holding(&[5, 15]), &[5, 15]),
+                round(2, RoundState::Finished, 80, holding(&[9, 19]), &[4, 4]),
+                // unfinished rounds don't count
+                round(3, RoundState::InProgress, 0, new_player_stats(players, params.resource_names()), &[50, 50]),
+            ],
+            scores: None,
+        }
+    }
+
+    #[test]
+    fn gini_of_equal_holdings_is_zero() {
+        assert_eq!(gini(&holding(&[10, 10, 10])), Fixed::ZERO);
+        assert_eq!(gini(&PlayerStats::new()), Fixed::ZERO);
+        assert_eq!(gini(&holding(&[0, 0])), Fixed::ZERO);
+    }
+
+    #[test]
+    fn gini_of_one_player_holding_everything() {
+        assert_eq!(gini(&holding(&[10, 0])), Fixed::from_raw(500));
+        assert_eq!(gini(&holding(&[30, 0, 0])), Fixed::from_raw(667));
+        // debts don't count as negative holdings
+        assert_eq!(gini(&holding(&[10, -10])), Fixed::from_raw(500));
+    }
+
+    #[test]
+    fn metrics_of_a_session() {
+        let metrics = session_metrics(&export());
+        assert_eq!(metrics.harvested["fish"], Fixed::from_int(28));
+        // 10% of 100 per round, over 2 rounds
+        assert_eq!(metrics.max_sustainable_yield["fish"], Fixed::from_int(20));
+        assert_eq!(metrics.harvest_ratio["fish"], Fixed::from_raw(1_400));
+        assert_eq!(metrics.rounds_survived, 2);
+        // held 9 and 19 after the last finished round: 20 / (2 * 2 * 28)
+        assert_eq!(metrics.gini, Fixed::from_raw(179));
+    }
+
+    #[test]
+    fn cooperation_counts_rounds_within_the_sustainable_share() {
+        let metrics = session_metrics(&export());
+        // sustainable shares are 5 in round one and 4.4 in round two
+        let index_of = |seat: u8| metrics.cooperation.iter().find(|c| c.agent == agent(seat)).unwrap().index;
+        assert_eq!(index_of(1), Fixed::ONE);
+        assert_eq!(index_of(2), Fixed::from_raw(500));
+    }
+}
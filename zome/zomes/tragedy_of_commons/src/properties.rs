@@ -0,0 +1,92 @@
+use crate::{
+    game_session::{GameParams, GameSession},
+    types::ResourceAmount,
+};
+use hdk::prelude::*;
+use holo_hash::AgentPubKeyB64;
+
+/// Properties set in the dna.yaml of a deployment
+#[derive(Serialize, Deserialize, SerializedBytes, Debug, Clone)]
+pub struct DnaProperties {
+    /// agent that runs the research with this deployment, None if there is no researcher.
+    /// The researcher can list all sessions, export any session, pause, resume or abort
+    /// sessions and is the only one who can publish presets.
+    #[serde(default)]
+    pub researcher_address: Option<AgentPubKeyB64>,
+    /// agent that set up the deployment, can sign invites like the researcher
+    #[serde(default)]
+    pub progenitor: Option<AgentPubKeyB64>,
+    /// only agents with an invite signed by the progenitor or the researcher can join,
+    /// see membrane.rs
+    #[serde(default)]
+    pub require_invite: bool,
+    /// params of sessions started with SessionParams::Default, None to use the built-in ones
+    #[serde(default)]
+    pub default_params: Option<GameParams>,
+    #[serde(default)]
+    pub limits: SessionLimits,
+}
+
+/// Hard limits every session of a deployment has to stay within, None means no limit.
+/// Amounts are in thousandths, like everywhere else.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SessionLimits {
+    #[serde(default)]
+    pub max_players: Option<u32>,
+    #[serde(default)]
+    pub max_rounds: Option<u32>,
+    #[serde(default)]
+    pub min_start_amount: Option<ResourceAmount>,
+    #[serde(default)]
+    pub max_start_amount: Option<ResourceAmount>,
+}
+
+impl SessionLimits {
+    pub fn check(&self, game_session: &GameSession) -> Result<(), String> {
+        if let Some(max_players) = self.max_players {
+            if game_session.players.len() > max_players as usize {
+                return Err(format!("A session can have at most {} players", max_players));
+            }
+        }
+        self.check_params(&game_session.game_params)
+    }
+
+    pub fn check_params(&self, params: &GameParams) -> Result<(), String> {
+        if let Some(max_rounds) = self.max_rounds {
+            if params.num_rounds > max_rounds {
+                return Err(format!("A session can have at most {} rounds", max_rounds));
+            }
+        }
+        for resource in params.resources.iter() {
+            let too_low = self.min_start_amount.map_or(false, |min| resource.start_amount < min);
+            let too_high = self.max_start_amount.map_or(false, |max| resource.start_amount > max);
+            if too_low || too_high {
+                return Err(format!(
+                    "Start amount of {} has to be between {} and {}",
+                    resource.name,
+                    self.min_start_amount.map_or("-".into(), |min| min.to_string()),
+                    self.max_start_amount.map_or("-".into(), |max| max.to_string()),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DnaProperties {
+    pub fn get() -> ExternResult<Self> {
+        let prop: DnaProperties = zome_info()?.properties.try_into()?;
+        Ok(prop)
+    }
+}
+
+pub fn is_researcher(agent: &AgentPubKey) -> ExternResult<bool> {
+    match DnaProperties::get()?.researcher_address {
+        Some(researcher) => Ok(agent == &AgentPubKey::from(researcher)),
+        None => Ok(false),
+    }
+}
+
+pub fn am_i_researcher() -> ExternResult<bool> {
+    is_researcher(&agent_info()?.agent_initial_pubkey)
+}
@@ -0,0 +1,218 @@
+use crate::{
+    bots::{bot_plan, bot_seat, may_play_for},
+    fixed_point::Fixed,
+    game_round::{GameRound, RoundState},
+    game_session::GameSession,
+    session_control::ensure_session_running,
+    types::{ResourceAmount, ResourcePools},
+    utils::{entry_hash_from_element, try_from_element, try_get_latest_element},
+};
+use hdk::prelude::*;
+use holo_hash::*;
+
+/// How the votes of all players are turned into the binding quota of a round
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum QuotaRule {
+    /// the median cap wins, with an even number of votes the lower of the two middle ones
+    Median,
+    /// a cap that more than half of the players voted for wins, without such a cap
+    /// the median is used
+    Majority,
+}
+
+/// Before the moves of a round every player votes on the maximum amount
+/// a single player may take from every resource pool.
+#[hdk_entry(id = "quota_vote", visibility = "public")]
+pub struct QuotaVote {
+    pub owner: AgentPubKey,
+    pub round: EntryHash,
+    pub caps: ResourcePools,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct QuotaVoteInput {
+    pub caps: ResourcePools,
+    pub current_round_header_hash: HeaderHashB64,
+}
+
+/// Function to call while the round is in RoundState::Voting
+#[hdk_extern]
+pub fn new_quota_vote(input: QuotaVoteInput) -> ExternResult<HeaderHash> {
+    new_quota_vote_for(agent_info()?.agent_initial_pubkey, input)
+}
+
+/// Casts the vote of the given seat, which is the caller itself unless it plays a bot seat
+pub fn new_quota_vote_for(owner: AgentPubKey, input: QuotaVoteInput) -> ExternResult<HeaderHash> {
+    let game_round_header_hash: HeaderHash = input.current_round_header_hash.into();
+    let game_round_element = match get(game_round_header_hash, GetOptions::content())? {
+        Some(element) => element,
+        None => return Err(WasmError::Guest("Current round not found".into())),
+    };
+    let game_round_entry_hash = entry_hash_from_element(game_round_element)?;
+
+    let latest_round: GameRound = try_from_element(try_get_latest_element(game_round_entry_hash.clone())?)?;
+    if latest_round.round_state != RoundState::Voting {
+        return Err(WasmError::Guest("Cannot vote: quota of this round is already decided".into()));
+    }
+    ensure_session_running(latest_round.session_header_hash.clone())?;
+
+    let (existing_votes, _) = get_round_votes(game_round_entry_hash.clone())?;
+    if existing_votes.iter().any(|v| v.owner == owner) {
+        return Err(WasmError::Guest("Cannot vote: you already voted in this round".into()));
+    }
+
+    let quota_vote = QuotaVote {
+        owner,
+        round: game_round_entry_hash.clone(),
+        caps: input.caps,
+    };
+    let header_hash_quota_vote = create_entry(&quota_vote)?;
+    let entry_hash_quota_vote = hash_entry(&quota_vote)?;
+    create_link(
+        game_round_entry_hash,
+        entry_hash_quota_vote,
+        LinkTag::new("quota_vote"),
+    )?;
+    Ok(header_hash_quota_vote)
+}
+
+/// Retrieves the votes linked from a round, only the first one of every player counts
+pub fn get_round_votes(round_entry_hash: EntryHash) -> ExternResult<(Vec<QuotaVote>, Vec<EntryHash>)> {
+    let links = get_links(round_entry_hash, Some(LinkTag::new("quota_vote")))?;
+    let mut votes: Vec<QuotaVote> = vec![];
+    let mut vote_entry_hashes: Vec<EntryHash> = vec![];
+    for link in links.into_inner() {
+        let item: QuotaVote = match get(link.target.clone(), GetOptions::default())? {
+            Some(element) => try_from_element(element)?,
+            None => return Err(WasmError::Guest("Cannot extract quota vote from link".into())),
+        };
+        if votes.iter().any(|v| v.owner == item.owner) {
+            continue;
+        }
+        votes.push(item);
+        vote_entry_hashes.push(link.target);
+    }
+    Ok((votes, vote_entry_hashes))
+}
+
+/// Decides the cap of every resource pool in resource_names from the votes
+pub fn decide_quota(rule: QuotaRule, resource_names: Vec<String>, votes: &[QuotaVote]) -> ResourcePools {
+    let mut quota = ResourcePools::new();
+    for name in resource_names {
+        let mut caps: Vec<ResourceAmount> = votes
+            .iter()
+            .map(|v| v.caps.get(&name).copied().unwrap_or(Fixed::ZERO))
+            .collect();
+        if caps.is_empty() {
+            continue;
+        }
+        caps.sort();
+        let median = caps[(caps.len() - 1) / 2];
+        let cap = match rule {
+            QuotaRule::Median => median,
+            QuotaRule::Majority => caps
+                .iter()
+                .copied()
+                .find(|c| caps.iter().filter(|x| *x == c).count() * 2 > caps.len())
+                .unwrap_or(median),
+        };
+        quota.insert(name, cap);
+    }
+    quota
+}
+
+/// A vote can only be made by a player of a session with quota voting,
+/// and has to name a non negative cap for every resource pool.
+/// Votes of bot seats are cast by the agent that plays the bots of the round,
+/// and name the amounts the strategy of the seat plans to take as caps.
+#[hdk_extern]
+pub fn validate_create_entry_quota_vote(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let quota_vote: QuotaVote = try_from_element(data.element.clone())?;
+    let game_round: GameRound = match get(quota_vote.round.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![quota_vote.round.clone().into()])),
+    };
+    let session_header_hash: HeaderHash = game_round.session_header_hash.clone().into();
+    let game_session: GameSession = match get(session_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![session_header_hash.into()])),
+    };
+
+    if game_session.game_params.quota_voting.is_none() {
+        return Ok(ValidateCallbackResult::Invalid("Quota voting is not enabled for this session".into()));
+    }
+    if !game_session.players.contains(&AgentPubKeyB64::from(quota_vote.owner.clone())) {
+        return Ok(ValidateCallbackResult::Invalid("Only players can vote".into()));
+    }
+    if !may_play_for(&game_session, game_round.round_num, &quota_vote.owner, data.element.header().author()) {
+        return Ok(ValidateCallbackResult::Invalid("A vote can only be made by its owner".into()));
+    }
+    for name in game_session.game_params.resource_names() {
+        match quota_vote.caps.get(&name) {
+            Some(cap) if *cap >= Fixed::ZERO => {}
+            _ => return Ok(ValidateCallbackResult::Invalid("A vote needs a non negative cap for every resource".into())),
+        }
+    }
+    if let Some(seat) = bot_seat(&game_session, &quota_vote.owner) {
+        if quota_vote.caps != bot_plan(&game_session, seat, &game_round, &quota_vote.round, None) {
+            return Ok(ValidateCallbackResult::Invalid("A bot seat has to vote for what its strategy plans".into()));
+        }
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn votes(caps: &[i64]) -> Vec<QuotaVote> {
+        caps.iter()
+            .enumerate()
+            .map(|(seat, cap)| QuotaVote {
+                owner: AgentPubKey::from_raw_36(vec![seat as u8; 36]),
+                round: EntryHash::from_raw_36(vec![0; 36]),
+                caps: vec![("fish".to_string(), Fixed::from_int(*cap))].into_iter().collect(),
+            })
+            .collect()
+    }
+
+    fn quota(rule: QuotaRule, caps: &[i64]) -> Option<ResourceAmount> {
+        decide_quota(rule, vec!["fish".into()], &votes(caps)).get("fish").copied()
+    }
+
+    #[test]
+    fn median_takes_the_middle_cap() {
+        assert_eq!(quota(QuotaRule::Median, &[30, 10, 20]), Some(Fixed::from_int(20)));
+    }
+
+    #[test]
+    fn median_of_an_even_number_of_votes_takes_the_lower_middle() {
+        assert_eq!(quota(QuotaRule::Median, &[40, 10, 30, 20]), Some(Fixed::from_int(20)));
+    }
+
+    #[test]
+    fn majority_takes_the_cap_most_players_voted_for() {
+        assert_eq!(quota(QuotaRule::Majority, &[10, 30, 10]), Some(Fixed::from_int(10)));
+        assert_eq!(quota(QuotaRule::Majority, &[5, 30, 30, 30]), Some(Fixed::from_int(30)));
+    }
+
+    #[test]
+    fn majority_falls_back_to_the_median_on_a_tie() {
+        // half of the votes isn't a majority
+        assert_eq!(quota(QuotaRule::Majority, &[30, 10, 30, 10]), Some(Fixed::from_int(10)));
+        assert_eq!(quota(QuotaRule::Majority, &[10, 20, 30]), Some(Fixed::from_int(20)));
+    }
+
+    #[test]
+    fn missing_caps_count_as_zero() {
+        let mut votes = votes(&[10, 20, 30]);
+        votes[2].caps.clear();
+        let quota = decide_quota(QuotaRule::Median, vec!["fish".into()], &votes);
+        assert_eq!(quota.get("fish"), Some(&Fixed::from_int(10)));
+    }
+
+    #[test]
+    fn no_votes_give_no_quota() {
+        assert_eq!(quota(QuotaRule::Median, &[]), None);
+    }
+}
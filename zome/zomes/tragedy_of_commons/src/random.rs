@@ -0,0 +1,67 @@
+use crate::fixed_point::{Fixed, FIXED_SCALE};
+
+/// Deterministic pseudo random generator (splitmix64). Anything random in the game is drawn
+/// from a seed that every agent can derive from the DHT, so validators draw the same numbers
+/// as the agent that wrote the entry.
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: seed }
+    }
+
+    /// Derives the seed from raw hash bytes, e.g. the session and round entry hashes
+    pub fn from_bytes(sources: &[&[u8]]) -> Self {
+        let mut rng = SeededRng::new(0);
+        for bytes in sources {
+            for chunk in bytes.chunks(8) {
+                let mut word = [0u8; 8];
+                word[..chunk.len()].copy_from_slice(chunk);
+                rng.state ^= u64::from_le_bytes(word);
+                rng.next_u64();
+            }
+        }
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed value in [0, 1), in steps of one thousandth
+    pub fn next_unit(&mut self) -> Fixed {
+        Fixed::from_raw((self.next_u64() % FIXED_SCALE as u64) as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_the_same_numbers() {
+        let mut a = SeededRng::from_bytes(&[&[1, 2, 3], &[4, 5, 6, 7, 8, 9, 10, 11, 12]]);
+        let mut b = SeededRng::from_bytes(&[&[1, 2, 3], &[4, 5, 6, 7, 8, 9, 10, 11, 12]]);
+        let mut c = SeededRng::from_bytes(&[&[1, 2, 4], &[4, 5, 6, 7, 8, 9, 10, 11, 12]]);
+        let a: Vec<u64> = (0..10).map(|_| a.next_u64()).collect();
+        let b: Vec<u64> = (0..10).map(|_| b.next_u64()).collect();
+        let c: Vec<u64> = (0..10).map(|_| c.next_u64()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn units_are_between_zero_and_one() {
+        let mut rng = SeededRng::new(42);
+        for _ in 0..1000 {
+            let unit = rng.next_unit();
+            assert!(unit >= Fixed::ZERO && unit < Fixed::ONE);
+        }
+    }
+}
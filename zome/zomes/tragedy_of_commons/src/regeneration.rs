@@ -0,0 +1,249 @@
+use crate::fixed_point::Fixed;
+use crate::random::SeededRng;
+use crate::types::ResourceAmount;
+use hdk::prelude::*;
+
+/// How the commons regrows between two rounds. The model is applied to what is left
+/// after all moves of a round, and its result is what the next round starts with.
+/// All calculations are done on Fixed values, step by step in the order written below,
+/// so every agent rounds the same way.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RegenerationModel {
+    /// a constant amount is added every round: R + amount
+    Linear { amount: ResourceAmount },
+    /// resources grow by a percentage every round: R + (R * rate)
+    Exponential { rate: Fixed },
+    /// growth slows down close to the carrying capacity:
+    /// R + ((rate * R) * (capacity - R)) / capacity
+    Logistic { rate: Fixed, capacity: ResourceAmount },
+}
+
+impl RegenerationModel {
+    /// Returns the amount the next round starts with, a shock scales down the regrowth.
+    pub fn regenerate_with_shock(&self, amount: ResourceAmount, shock: Option<Shock>) -> ResourceAmount {
+        let regenerated = self.regenerate(amount);
+        match shock {
+            Some(shock) => amount + (regenerated - amount) * shock.regrowth_multiplier,
+            None => regenerated,
+        }
+    }
+
+    /// Returns the amount the next round starts with. A depleted commons doesn't recover.
+    pub fn regenerate(&self, amount: ResourceAmount) -> ResourceAmount {
+        if !amount.is_positive() {
+            return amount;
+        }
+        match *self {
+            RegenerationModel::Linear { amount: added } => amount + added,
+            RegenerationModel::Exponential { rate } => amount + amount * rate,
+            RegenerationModel::Logistic { rate, capacity } => {
+                let growth = (rate * amount) * (capacity - amount);
+                match growth.checked_div(capacity) {
+                    Some(growth) => amount + growth,
+                    None => amount,
+                }
+            }
+        }
+    }
+
+    /// Largest amount that can be taken every round without shrinking a pool that holds
+    /// `amount`. For logistic regrowth that's the classic maximum sustainable yield,
+    /// reached with the pool at half its capacity, whatever it holds now.
+    pub fn max_sustainable_yield(&self, amount: ResourceAmount) -> ResourceAmount {
+        match *self {
+            RegenerationModel::Linear { amount: added } => added,
+            RegenerationModel::Exponential { rate } => amount * rate,
+            RegenerationModel::Logistic { rate, capacity } => {
+                (rate * capacity).checked_div(Fixed::from_int(4)).unwrap_or(Fixed::ZERO)
+            }
+        }
+    }
+
+    /// Checks that the model parameters make sense, used when validating a GameSession
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            RegenerationModel::Linear { amount } if amount < Fixed::ZERO => {
+                Err("Linear regeneration can't add a negative amount".into())
+            }
+            RegenerationModel::Exponential { rate } if rate < Fixed::ZERO => {
+                Err("Exponential regeneration rate can't be negative".into())
+            }
+            RegenerationModel::Logistic { rate, .. } if rate < Fixed::ZERO => {
+                Err("Logistic regeneration rate can't be negative".into())
+            }
+            RegenerationModel::Logistic { capacity, .. } if !capacity.is_positive() => {
+                Err("Logistic regeneration needs a positive capacity".into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Optional environmental uncertainty, e.g. a bad year in which regrowth fails.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ShockParams {
+    /// chance that a round is hit by a shock, between 0 and 1
+    pub probability: Fixed,
+    /// share of the normal regrowth that still happens in a shock round, 0 means none
+    pub regrowth_multiplier: Fixed,
+}
+
+/// A shock that hit a round, recorded in the GameRound so the UI can explain it
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Shock {
+    /// the number drawn for this round, the shock happens when it is below the probability
+    pub roll: Fixed,
+    pub regrowth_multiplier: Fixed,
+}
+
+impl ShockParams {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.probability < Fixed::ZERO || self.probability > Fixed::ONE {
+            return Err("Shock probability has to be between 0 and 1".into());
+        }
+        if self.regrowth_multiplier < Fixed::ZERO {
+            return Err("Shock regrowth multiplier can't be negative".into());
+        }
+        Ok(())
+    }
+
+    /// Draws whether the round seeded by rng is hit by a shock
+    pub fn draw(&self, rng: &mut SeededRng) -> Option<Shock> {
+        let roll = rng.next_unit();
+        if roll < self.probability {
+            Some(Shock {
+                roll,
+                regrowth_multiplier: self.regrowth_multiplier,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Seed for the shock of a round. It only depends on entries every agent can get from
+/// the DHT, so validation can draw the same shock again.
+pub fn shock_rng(session_entry_hash: &EntryHash, round_entry_hash: &EntryHash) -> SeededRng {
+    SeededRng::from_bytes(&[session_entry_hash.get_raw_32(), round_entry_hash.get_raw_32()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(value: f64) -> Fixed {
+        Fixed::from_raw((value * 1000.0).round() as i64)
+    }
+
+    #[test]
+    fn linear_adds_a_constant_amount() {
+        let model = RegenerationModel::Linear { amount: fixed(10.0) };
+        assert_eq!(model.regenerate(fixed(100.0)), fixed(110.0));
+        assert_eq!(model.max_sustainable_yield(fixed(100.0)), fixed(10.0));
+    }
+
+    #[test]
+    fn exponential_grows_by_a_percentage() {
+        let model = RegenerationModel::Exponential { rate: fixed(0.1) };
+        assert_eq!(model.regenerate(fixed(100.0)), fixed(110.0));
+        // 0.5 * 0.1 = 0.05
+        assert_eq!(model.regenerate(fixed(0.5)), fixed(0.55));
+        assert_eq!(model.max_sustainable_yield(fixed(100.0)), fixed(10.0));
+    }
+
+    #[test]
+    fn logistic_growth_slows_down_at_the_capacity() {
+        let model = RegenerationModel::Logistic {
+            rate: fixed(0.5),
+            capacity: fixed(200.0),
+        };
+        // 100 + (0.5 * 100) * (200 - 100) / 200
+        assert_eq!(model.regenerate(fixed(100.0)), fixed(125.0));
+        assert_eq!(model.regenerate(fixed(200.0)), fixed(200.0));
+        // above the capacity the pool shrinks back
+        assert_eq!(model.regenerate(fixed(250.0)), fixed(218.75));
+        assert_eq!(model.max_sustainable_yield(fixed(10.0)), fixed(25.0));
+    }
+
+    #[test]
+    fn depleted_pools_dont_recover() {
+        let models = [
+            RegenerationModel::Linear { amount: fixed(10.0) },
+            RegenerationModel::Exponential { rate: fixed(0.1) },
+            RegenerationModel::Logistic {
+                rate: fixed(0.5),
+                capacity: fixed(200.0),
+            },
+        ];
+        for model in models.iter() {
+            assert_eq!(model.regenerate(Fixed::ZERO), Fixed::ZERO);
+            assert_eq!(model.regenerate(fixed(-5.0)), fixed(-5.0));
+        }
+    }
+
+    #[test]
+    fn shocks_scale_down_the_regrowth() {
+        let model = RegenerationModel::Exponential { rate: fixed(0.1) };
+        let shock = |multiplier| Shock {
+            roll: Fixed::ZERO,
+            regrowth_multiplier: multiplier,
+        };
+        assert_eq!(model.regenerate_with_shock(fixed(100.0), None), fixed(110.0));
+        assert_eq!(model.regenerate_with_shock(fixed(100.0), Some(shock(fixed(0.5)))), fixed(105.0));
+        assert_eq!(model.regenerate_with_shock(fixed(100.0), Some(shock(Fixed::ZERO))), fixed(100.0));
+    }
+
+    #[test]
+    fn invalid_models_are_rejected() {
+        assert!(RegenerationModel::Linear { amount: fixed(-1.0) }.validate().is_err());
+        assert!(RegenerationModel::Exponential { rate: fixed(-0.1) }.validate().is_err());
+        assert!(RegenerationModel::Logistic {
+            rate: fixed(0.5),
+            capacity: Fixed::ZERO
+        }
+        .validate()
+        .is_err());
+        assert!(RegenerationModel::Logistic {
+            rate: fixed(0.5),
+            capacity: fixed(200.0)
+        }
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn shocks_follow_the_probability() {
+        let never = ShockParams {
+            probability: Fixed::ZERO,
+            regrowth_multiplier: Fixed::ZERO,
+        };
+        let always = ShockParams {
+            probability: Fixed::ONE,
+            regrowth_multiplier: fixed(0.5),
+        };
+        let mut rng = SeededRng::new(7);
+        for _ in 0..100 {
+            assert_eq!(never.draw(&mut rng), None);
+            let shock = always.draw(&mut rng).unwrap();
+            assert!(shock.roll < Fixed::ONE);
+            assert_eq!(shock.regrowth_multiplier, fixed(0.5));
+        }
+    }
+
+    #[test]
+    fn shocks_are_the_same_for_every_agent() {
+        let session = EntryHash::from_raw_36(vec![1; 36]);
+        let round = EntryHash::from_raw_36(vec![2; 36]);
+        let other_round = EntryHash::from_raw_36(vec![3; 36]);
+        let params = ShockParams {
+            probability: fixed(0.5),
+            regrowth_multiplier: Fixed::ZERO,
+        };
+        let draws = |round: &EntryHash| {
+            let mut rng = shock_rng(&session, round);
+            (0..20).map(|_| params.draw(&mut rng)).collect::<Vec<_>>()
+        };
+        assert_eq!(draws(&round), draws(&round));
+        assert_ne!(draws(&round), draws(&other_round));
+    }
+}
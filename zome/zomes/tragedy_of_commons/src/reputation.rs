@@ -0,0 +1,75 @@
+use crate::{
+    game_session::{GameScores, GameSession},
+    leaderboard::get_player_history,
+    types::{new_player_stats, PlayerStats, ReputationAmount},
+    utils::try_from_element,
+};
+use hdk::prelude::*;
+use holo_hash::*;
+
+/// Reputation of an agent over all sessions they finished. It isn't stored anywhere,
+/// it's summed up from the validated GameScores of those sessions. A session that inherits
+/// reputation lists the scores its first round was summed from, so validators can sum them
+/// up again: scores can be left out, but not made up.
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct AgentReputation {
+    pub agent: AgentPubKeyB64,
+    pub reputation: ReputationAmount,
+    pub sessions: u32,
+}
+
+#[hdk_extern]
+pub fn get_reputation(agent: AgentPubKeyB64) -> ExternResult<AgentReputation> {
+    let history = get_player_history(agent.clone())?;
+    Ok(AgentReputation {
+        agent,
+        reputation: history.iter().map(|r| r.reputation).sum(),
+        sessions: history.len() as u32,
+    })
+}
+
+/// Scores of all finished sessions of the agents, each of them once
+pub fn career_scores(agents: &[AgentPubKeyB64]) -> ExternResult<Vec<(EntryHash, GameScores)>> {
+    let mut scores: Vec<(EntryHash, GameScores)> = vec![];
+    for agent in agents {
+        let links = get_links(AgentPubKey::from(agent.clone()).into(), Some(LinkTag::new("player_scores")))?;
+        for link in links.into_inner() {
+            if scores.iter().any(|(entry_hash, _)| entry_hash == &link.target) {
+                continue;
+            }
+            if let Some(element) = get(link.target.clone(), GetOptions::default())? {
+                scores.push((link.target, try_from_element(element)?));
+            }
+        }
+    }
+    Ok(scores)
+}
+
+/// Stats the players of a session start the first round with: nothing, and with
+/// inherit_reputation the reputation they earned in the given scores.
+/// Scores of the same session only count once.
+pub fn starting_stats(game_session: &GameSession, scores: &[GameScores]) -> PlayerStats {
+    let mut stats = new_player_stats(game_session.players.clone(), game_session.game_params.resource_names());
+    if !game_session.game_params.inherit_reputation {
+        return stats;
+    }
+    let mut sessions: Vec<&EntryHashB64> = vec![];
+    for game_scores in scores {
+        if sessions.contains(&&game_scores.session) {
+            continue;
+        }
+        sessions.push(&game_scores.session);
+        for (agent, stat) in stats.iter_mut() {
+            if let Some(earned) = game_scores.stats.get(agent) {
+                stat.reputation += earned.reputation;
+            }
+        }
+    }
+    stats
+}
+
+/// Reputation of several agents at once, e.g. for choosing who to invite to a session
+#[hdk_extern]
+pub fn get_reputations(agents: Vec<AgentPubKeyB64>) -> ExternResult<Vec<AgentReputation>> {
+    agents.into_iter().map(get_reputation).collect()
+}
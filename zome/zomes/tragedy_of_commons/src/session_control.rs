@@ -0,0 +1,161 @@
+use crate::{
+    game_session::{GameSession, GameSignal},
+    properties::is_researcher,
+    spectators::signal_recipients,
+    utils::{entry_hash_from_element, try_from_element},
+};
+use hdk::prelude::*;
+use holo_hash::*;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SessionAction {
+    Pause,
+    Resume,
+    Abort,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SessionStatus {
+    Running,
+    /// no moves, votes or sanctions and no closing of rounds until the session is resumed
+    Paused,
+    /// the session is over for good, without GameScores
+    Aborted,
+}
+
+impl SessionStatus {
+    /// Status after the action, None if the action isn't possible in this status
+    pub fn apply(self, action: SessionAction) -> Option<SessionStatus> {
+        match (self, action) {
+            (SessionStatus::Running, SessionAction::Pause) => Some(SessionStatus::Paused),
+            (SessionStatus::Paused, SessionAction::Resume) => Some(SessionStatus::Running),
+            (SessionStatus::Running, SessionAction::Abort) | (SessionStatus::Paused, SessionAction::Abort) => {
+                Some(SessionStatus::Aborted)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Sent to all players and spectators when a session is paused, resumed or aborted
+#[derive(Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct SignalPayloadSessionStatus {
+    pub game_session_header_hash: HeaderHashB64,
+    pub action: SessionAction,
+    pub status: SessionStatus,
+    pub author: AgentPubKeyB64,
+}
+
+/// Sessions themselves are never updated, pausing, resuming or aborting one is recorded
+/// as a SessionControl linked from the session. The status is the result of all of them.
+#[hdk_entry(id = "session_control", visibility = "public")]
+pub struct SessionControl {
+    pub session_header_hash: HeaderHashB64,
+    pub action: SessionAction,
+    pub author: AgentPubKeyB64,
+    /// system time of the author, only there so that repeating an action makes a new entry.
+    /// Controls are ordered by the timestamp of their header, see get_session_status.
+    pub at: Duration,
+}
+
+#[hdk_extern]
+pub fn pause_session(session_header_hash: HeaderHashB64) -> ExternResult<SessionStatus> {
+    control_session(session_header_hash, SessionAction::Pause)
+}
+
+#[hdk_extern]
+pub fn resume_session(session_header_hash: HeaderHashB64) -> ExternResult<SessionStatus> {
+    control_session(session_header_hash, SessionAction::Resume)
+}
+
+#[hdk_extern]
+pub fn abort_session(session_header_hash: HeaderHashB64) -> ExternResult<SessionStatus> {
+    control_session(session_header_hash, SessionAction::Abort)
+}
+
+#[hdk_extern]
+pub fn get_session_status(session_header_hash: HeaderHashB64) -> ExternResult<SessionStatus> {
+    let mut controls: Vec<(Timestamp, SessionControl)> = vec![];
+    for link in get_links(session_entry_hash(session_header_hash)?, Some(LinkTag::new("session_control")))?.into_inner() {
+        if let Some(element) = get(link.target, GetOptions::default())? {
+            controls.push((element.header().timestamp(), try_from_element(element)?));
+        }
+    }
+    controls.sort_by_key(|(timestamp, _)| *timestamp);
+    Ok(controls
+        .iter()
+        .fold(SessionStatus::Running, |status, (_, control)| status.apply(control.action).unwrap_or(status)))
+}
+
+/// Called before anything that moves a session forward
+pub fn ensure_session_running(session_header_hash: HeaderHashB64) -> ExternResult<()> {
+    match get_session_status(session_header_hash)? {
+        SessionStatus::Running => Ok(()),
+        SessionStatus::Paused => Err(WasmError::Guest("Session is paused".into())),
+        SessionStatus::Aborted => Err(WasmError::Guest("Session is aborted".into())),
+    }
+}
+
+fn control_session(session_header_hash: HeaderHashB64, action: SessionAction) -> ExternResult<SessionStatus> {
+    let game_session: GameSession = match get(HeaderHash::from(session_header_hash.clone()), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Err(WasmError::Guest("Session not found".into())),
+    };
+    let me = agent_info()?.agent_initial_pubkey;
+    if !can_control(&game_session, &me)? {
+        return Err(WasmError::Guest("Only the owner of a session or the researcher can pause, resume or abort it".into()));
+    }
+    let status = get_session_status(session_header_hash.clone())?;
+    let next_status = match status.apply(action) {
+        Some(next_status) => next_status,
+        None => return Err(WasmError::Guest(format!("Cannot {:?} a session that is {:?}", action, status))),
+    };
+    let control = SessionControl {
+        session_header_hash: session_header_hash.clone(),
+        action,
+        author: me.clone().into(),
+        at: sys_time()?,
+    };
+    create_entry(&control)?;
+    create_link(hash_entry(&game_session)?, hash_entry(&control)?, LinkTag::new("session_control"))?;
+
+    let signal = ExternIO::encode(GameSignal::SessionStatusChanged(SignalPayloadSessionStatus {
+        game_session_header_hash: session_header_hash,
+        action,
+        status: next_status,
+        author: me.into(),
+    }))?;
+    remote_signal(signal, signal_recipients(&game_session)?)?;
+    Ok(next_status)
+}
+
+fn can_control(game_session: &GameSession, agent: &AgentPubKey) -> ExternResult<bool> {
+    Ok(agent == &AgentPubKey::from(game_session.owner.clone()) || is_researcher(agent)?)
+}
+
+fn session_entry_hash(session_header_hash: HeaderHashB64) -> ExternResult<EntryHash> {
+    match get(HeaderHash::from(session_header_hash), GetOptions::content())? {
+        Some(element) => entry_hash_from_element(element),
+        None => Err(WasmError::Guest("Session not found".into())),
+    }
+}
+
+/// Only the owner of the session or the researcher can pause, resume or abort it
+#[hdk_extern]
+pub fn validate_create_entry_session_control(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let control: SessionControl = try_from_element(data.element.clone())?;
+    let author = data.element.header().author();
+    if author != &AgentPubKey::from(control.author.clone()) {
+        return Ok(ValidateCallbackResult::Invalid("A session control can only be made by its author".into()));
+    }
+    let session_header_hash: HeaderHash = control.session_header_hash.into();
+    let game_session: GameSession = match get(session_header_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![session_header_hash.into()])),
+    };
+    if !can_control(&game_session, author)? {
+        return Ok(ValidateCallbackResult::Invalid("Only the owner of a session or the researcher can pause, resume or abort it".into()));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
@@ -0,0 +1,86 @@
+use crate::{
+    game_session::GameSession,
+    utils::{convert_keys_from_b64, entry_hash_from_element, try_from_element},
+};
+use hdk::prelude::*;
+use holo_hash::*;
+
+/// Lets the caller watch a public session: from now on they get all signals of the
+/// session, but as they're not a player they can't make any moves.
+#[hdk_extern]
+pub fn join_as_spectator(session_header_hash: HeaderHashB64) -> ExternResult<HeaderHashB64> {
+    let element = match get(HeaderHash::from(session_header_hash), GetOptions::content())? {
+        Some(element) => element,
+        None => return Err(WasmError::Guest("Session not found".into())),
+    };
+    let game_session_entry_hash = entry_hash_from_element(element.clone())?;
+    let game_session: GameSession = try_from_element(element)?;
+    if !game_session.public {
+        return Err(WasmError::Guest("Only public sessions can be watched".into()));
+    }
+    let me = agent_info()?.agent_initial_pubkey;
+    if game_session.players.contains(&AgentPubKeyB64::from(me.clone())) {
+        return Err(WasmError::Guest("Players can't watch their own session".into()));
+    }
+    let link_header_hash = create_link(game_session_entry_hash, me.into(), LinkTag::new("spectator"))?;
+    Ok(link_header_hash.into())
+}
+
+/// Only agents themselves can join a session as spectator, and only if the session is
+/// public and they aren't one of its players. See join_as_spectator.
+pub fn validate_create_link_spectator(data: ValidateCreateLinkData) -> ExternResult<ValidateLinkCallbackResult> {
+    let game_session = match GameSession::try_from(data.base) {
+        Ok(game_session) => game_session,
+        Err(_) => return Ok(ValidateLinkCallbackResult::Invalid("Spectators can only join a session".into())),
+    };
+    // links point at the agent key, retyped as an entry hash
+    let spectator: AgentPubKey = data.link_add.target_address.retype(hash_type::Agent);
+    if spectator != data.link_add.author {
+        return Ok(ValidateLinkCallbackResult::Invalid("Agents can only join as spectator themselves".into()));
+    }
+    if !game_session.public {
+        return Ok(ValidateLinkCallbackResult::Invalid("Only public sessions can be watched".into()));
+    }
+    if game_session.players.contains(&AgentPubKeyB64::from(spectator)) {
+        return Ok(ValidateLinkCallbackResult::Invalid("Players can't watch their own session".into()));
+    }
+    Ok(ValidateLinkCallbackResult::Valid)
+}
+
+/// Spectators of a session, the ones listed in the session and the ones that joined later
+#[hdk_extern]
+pub fn get_spectators(session_header_hash: HeaderHashB64) -> ExternResult<Vec<AgentPubKeyB64>> {
+    let element = match get(HeaderHash::from(session_header_hash), GetOptions::content())? {
+        Some(element) => element,
+        None => return Err(WasmError::Guest("Session not found".into())),
+    };
+    let game_session: GameSession = try_from_element(element)?;
+    Ok(session_spectators(&game_session)?
+        .into_iter()
+        .map(AgentPubKeyB64::from)
+        .collect())
+}
+
+/// Everybody that gets the signals of a session: its players and its spectators
+pub fn signal_recipients(game_session: &GameSession) -> ExternResult<Vec<AgentPubKey>> {
+    let mut recipients = convert_keys_from_b64(game_session.players.clone());
+    for spectator in session_spectators(game_session)? {
+        if !recipients.contains(&spectator) {
+            recipients.push(spectator);
+        }
+    }
+    Ok(recipients)
+}
+
+fn session_spectators(game_session: &GameSession) -> ExternResult<Vec<AgentPubKey>> {
+    let mut spectators = convert_keys_from_b64(game_session.spectators.clone());
+    let links = get_links(hash_entry(game_session)?, Some(LinkTag::new("spectator")))?;
+    for link in links.into_inner() {
+        // links point at the agent key, retyped as an entry hash
+        let spectator: AgentPubKey = link.target.retype(hash_type::Agent);
+        if !spectators.contains(&spectator) {
+            spectators.push(spectator);
+        }
+    }
+    Ok(spectators)
+}
@@ -0,0 +1,290 @@
+use crate::{
+    fixed_point::Fixed,
+    game_session::{resolve_params, start_session, GameParams, GameScores, GameSession, SessionParams},
+    types::ReputationAmount,
+    utils::{now, try_from_element, try_get_and_convert},
+};
+use hdk::prelude::*;
+use holo_hash::*;
+
+/// A league in which a pool of players plays several stages of sessions with the same params.
+/// In every stage the pool is seated round-robin at tables of session_size players,
+/// and every table plays its own GameSession.
+#[hdk_entry(id = "tournament", visibility = "public")]
+#[derive(Clone)]
+pub struct Tournament {
+    pub owner: AgentPubKeyB64,
+    pub name: String,
+    pub players: Vec<AgentPubKeyB64>,
+    pub session_size: u32,
+    pub stages: u32,
+    pub game_params: GameParams,
+    pub preset: Option<EntryHashB64>,
+}
+
+/// Seat of a session in a tournament, stored in the GameSession
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TournamentTable {
+    pub tournament: EntryHashB64,
+    pub stage: u32,
+    pub table: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct TournamentInput {
+    pub name: String,
+    pub players: Vec<AgentPubKeyB64>,
+    pub session_size: u32,
+    pub stages: u32,
+    pub game_params: SessionParams,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct StartStageInput {
+    pub tournament_hash: EntryHashB64,
+    pub stage: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, SerializedBytes)]
+pub struct TournamentStanding {
+    pub agent: AgentPubKeyB64,
+    /// finished sessions of the tournament the player played in
+    pub sessions_played: u32,
+    /// sum of the scores of all finished sessions, see GameParams::score
+    pub score: Fixed,
+    pub reputation: ReputationAmount,
+}
+
+/// Creates a tournament, its stages are started one by one with start_tournament_stage
+#[hdk_extern]
+pub fn create_tournament(input: TournamentInput) -> ExternResult<EntryHashB64> {
+    let (game_params, preset) = resolve_params(input.game_params)?;
+    let tournament = Tournament {
+        owner: AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey),
+        name: input.name,
+        players: input.players,
+        session_size: input.session_size,
+        stages: input.stages,
+        game_params,
+        preset,
+    };
+    create_entry(&tournament)?;
+    Ok(hash_entry(&tournament)?.into())
+}
+
+/// Starts a session for every table of a stage, only the owner of the tournament can do this.
+/// Returns the header hashes of the first rounds of the new sessions.
+#[hdk_extern]
+pub fn start_tournament_stage(input: StartStageInput) -> ExternResult<Vec<HeaderHashB64>> {
+    let tournament_entry_hash: EntryHash = input.tournament_hash.clone().into();
+    let tournament: Tournament = try_get_and_convert(tournament_entry_hash.clone())?;
+    let me = AgentPubKeyB64::from(agent_info()?.agent_initial_pubkey);
+    if tournament.owner != me {
+        return Err(WasmError::Guest("Only the owner of a tournament can start its stages".into()));
+    }
+    if input.stage >= tournament.stages {
+        return Err(WasmError::Guest("Tournament doesn't have this stage".into()));
+    }
+    let started = get_tournament_sessions(tournament_entry_hash.clone())?
+        .iter()
+        .any(|(session, _)| session.tournament.as_ref().map(|t| t.stage) == Some(input.stage));
+    if started {
+        return Err(WasmError::Guest("This stage is already started".into()));
+    }
+
+    let mut round_header_hashes: Vec<HeaderHashB64> = vec![];
+    let tables = seat_players(&tournament.players, tournament.session_size as usize, input.stage);
+    for (table, players) in tables.into_iter().enumerate() {
+        let gs = GameSession {
+            owner: me.clone(),
+            created_at: now()?,
+            game_params: tournament.game_params.clone(),
+            players,
+            bots: vec![],
+            preset: tournament.preset.clone(),
+            previous_session: None,
+            tournament: Some(TournamentTable {
+                tournament: input.tournament_hash.clone(),
+                stage: input.stage,
+                table: table as u32,
+            }),
+            spectators: vec![],
+            public: false,
+            inherited_scores: vec![],
+        };
+        let (round_header_hash, game_session_entry_hash) = start_session(gs)?;
+        round_header_hashes.push(round_header_hash.into());
+        create_link(
+            tournament_entry_hash.clone(),
+            game_session_entry_hash,
+            LinkTag::new("tournament_session"),
+        )?;
+    }
+    Ok(round_header_hashes)
+}
+
+/// Standings of all players of a tournament, best score first.
+/// Only sessions that are over, i.e. that have GameScores, count.
+#[hdk_extern]
+pub fn get_tournament_standings(tournament_hash: EntryHashB64) -> ExternResult<Vec<TournamentStanding>> {
+    let tournament_entry_hash: EntryHash = tournament_hash.into();
+    let tournament: Tournament = try_get_and_convert(tournament_entry_hash.clone())?;
+    let mut standings: Vec<TournamentStanding> = tournament
+        .players
+        .iter()
+        .map(|agent| TournamentStanding {
+            agent: agent.clone(),
+            sessions_played: 0,
+            score: Fixed::ZERO,
+            reputation: 0,
+        })
+        .collect();
+
+    for (session, session_entry_hash) in get_tournament_sessions(tournament_entry_hash)? {
+        let links = get_links(session_entry_hash, Some(LinkTag::new("game_scores")))?;
+        let game_scores: GameScores = match links.into_inner().into_iter().next() {
+            Some(link) => try_get_and_convert(link.target)?,
+            None => continue,
+        };
+        for (agent, stat) in game_scores.stats.iter() {
+            if let Some(standing) = standings.iter_mut().find(|s| &s.agent == agent) {
+                standing.sessions_played += 1;
+                standing.score += session.game_params.score(stat);
+                standing.reputation += stat.reputation;
+            }
+        }
+    }
+    standings.sort_by(|a, b| b.score.cmp(&a.score));
+    Ok(standings)
+}
+
+/// Sessions linked from a tournament, with their entry hashes
+fn get_tournament_sessions(tournament_entry_hash: EntryHash) -> ExternResult<Vec<(GameSession, EntryHash)>> {
+    let links = get_links(tournament_entry_hash, Some(LinkTag::new("tournament_session")))?;
+    let mut sessions: Vec<(GameSession, EntryHash)> = vec![];
+    for link in links.into_inner() {
+        let session: GameSession = try_get_and_convert(link.target.clone())?;
+        sessions.push((session, link.target));
+    }
+    Ok(sessions)
+}
+
+/// Round-robin seating of a stage: the pool is rotated around its first player by one seat
+/// per stage and then dealt over the tables, so players meet different opponents every
+/// stage. Players that don't fill a complete table sit out the stage.
+pub fn seat_players(players: &[AgentPubKeyB64], session_size: usize, stage: u32) -> Vec<Vec<AgentPubKeyB64>> {
+    if session_size == 0 || players.len() < session_size {
+        return vec![];
+    }
+    let tables = players.len() / session_size;
+    let mut order: Vec<AgentPubKeyB64> = players.to_vec();
+    let rotating = order.len() - 1;
+    if rotating > 0 {
+        order[1..].rotate_right(stage as usize % rotating);
+    }
+    (0..tables)
+        .map(|table| (0..session_size).map(|seat| order[seat * tables + table].clone()).collect())
+        .collect()
+}
+
+/// A tournament needs a name, a pool of distinct players that fills at least one table,
+/// and params a session can be started with
+#[hdk_extern]
+pub fn validate_create_entry_tournament(data: ValidateData) -> ExternResult<ValidateCallbackResult> {
+    let tournament: Tournament = try_from_element(data.element.clone())?;
+    if data.element.header().author() != &AgentPubKey::from(tournament.owner.clone()) {
+        return Ok(ValidateCallbackResult::Invalid("A tournament can only be created by its owner".into()));
+    }
+    if tournament.name.trim().is_empty() {
+        return Ok(ValidateCallbackResult::Invalid("A tournament needs a name".into()));
+    }
+    if tournament.session_size < 2 || tournament.players.len() < tournament.session_size as usize {
+        return Ok(ValidateCallbackResult::Invalid("A tournament needs at least one table of two or more players".into()));
+    }
+    if tournament.stages == 0 {
+        return Ok(ValidateCallbackResult::Invalid("A tournament needs at least one stage".into()));
+    }
+    for (i, player) in tournament.players.iter().enumerate() {
+        if tournament.players[..i].contains(player) {
+            return Ok(ValidateCallbackResult::Invalid("A player can only join a tournament once".into()));
+        }
+    }
+    if let Err(reason) = tournament.game_params.validate() {
+        return Ok(ValidateCallbackResult::Invalid(reason));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
+/// A tournament session has to be started by the owner of the tournament,
+/// with the players seated at its table and the params of the tournament.
+pub fn validate_tournament_table(game_session: &GameSession, table: &TournamentTable, author: &AgentPubKey) -> ExternResult<ValidateCallbackResult> {
+    let tournament_entry_hash: EntryHash = table.tournament.clone().into();
+    let tournament: Tournament = match get(tournament_entry_hash.clone(), GetOptions::content())? {
+        Some(element) => try_from_element(element)?,
+        None => return Ok(ValidateCallbackResult::UnresolvedDependencies(vec![tournament_entry_hash.into()])),
+    };
+    if author != &AgentPubKey::from(tournament.owner.clone()) || game_session.owner != tournament.owner {
+        return Ok(ValidateCallbackResult::Invalid("Only the owner of a tournament can start its sessions".into()));
+    }
+    if table.stage >= tournament.stages {
+        return Ok(ValidateCallbackResult::Invalid("Tournament doesn't have this stage".into()));
+    }
+    let seating = seat_players(&tournament.players, tournament.session_size as usize, table.stage);
+    if seating.get(table.table as usize) != Some(&game_session.players) {
+        return Ok(ValidateCallbackResult::Invalid("Session players don't match the seating of the tournament".into()));
+    }
+    if game_session.game_params != tournament.game_params || !game_session.bots.is_empty() {
+        return Ok(ValidateCallbackResult::Invalid("A tournament session has to use the params of the tournament".into()));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(size: u8) -> Vec<AgentPubKeyB64> {
+        (1..=size).map(|seat| AgentPubKey::from_raw_36(vec![seat; 36]).into()).collect()
+    }
+
+    #[test]
+    fn first_stage_deals_the_pool_over_the_tables() {
+        let players = pool(6);
+        let tables = seat_players(&players, 3, 0);
+        assert_eq!(tables, vec![
+            vec![players[0].clone(), players[2].clone(), players[4].clone()],
+            vec![players[1].clone(), players[3].clone(), players[5].clone()],
+        ]);
+    }
+
+    #[test]
+    fn later_stages_rotate_around_the_first_player() {
+        let players = pool(6);
+        let tables = seat_players(&players, 3, 1);
+        assert_eq!(tables, vec![
+            vec![players[0].clone(), players[1].clone(), players[3].clone()],
+            vec![players[5].clone(), players[2].clone(), players[4].clone()],
+        ]);
+        // after a full rotation the seating repeats
+        assert_eq!(seat_players(&players, 3, 5), seat_players(&players, 3, 0));
+    }
+
+    #[test]
+    fn every_player_sits_at_most_once_per_stage() {
+        let players = pool(7);
+        for stage in 0..6 {
+            let seated: Vec<AgentPubKeyB64> = seat_players(&players, 3, stage).into_iter().flatten().collect();
+            // one player doesn't fill a complete table and sits out
+            assert_eq!(seated.len(), 6);
+            for (i, player) in seated.iter().enumerate() {
+                assert!(!seated[..i].contains(player));
+            }
+        }
+    }
+
+    #[test]
+    fn too_few_players_fill_no_table() {
+        assert!(seat_players(&pool(2), 3, 0).is_empty());
+        assert!(seat_players(&pool(2), 0, 0).is_empty());
+    }
+}
@@ -1,19 +1,51 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 // NOTE(e-nastasia): I don't like that we include everything here, I'd like to make
 // that import more precise. But maybe that's ok?
 use hdk::prelude::*;
 use holo_hash::AgentPubKeyB64;
 
-pub type ResourceAmount = i32;
+use crate::fixed_point::Fixed;
+
+/// Resources are fixed-point so that fractional regrowth doesn't get lost between rounds
+pub type ResourceAmount = Fixed;
 pub type ReputationAmount = i32;
+/// Amount per named resource pool. It's a BTreeMap so that entries containing it
+/// always serialize (and hash) the same way.
+pub type ResourcePools = BTreeMap<String, ResourceAmount>;
 // TODO(e-nastasia): how do we make this datatype serializable?
 
-pub type PlayerStats = HashMap<AgentPubKeyB64, (ResourceAmount, ReputationAmount)>;
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlayerStat {
+    /// resources the player holds, everything they took in all rounds so far
+    /// minus what they spent on or lost to sanctions
+    pub resources: ResourcePools,
+    /// what the player took in the latest round
+    pub last_taken: ResourcePools,
+    pub reputation: ReputationAmount,
+    /// resources the player spent on sanctioning others
+    pub sanctions_spent: ResourcePools,
+    /// resources and reputation the player lost to sanctions by others
+    pub sanctioned_resources: ResourcePools,
+    pub sanctioned_reputation: ReputationAmount,
+}
+
+pub type PlayerStats = HashMap<AgentPubKeyB64, PlayerStat>;
 
 /// Generates empty PlayerStats with 0 values for every player in players
-pub fn new_player_stats(players: Vec<AgentPubKeyB64>) -> PlayerStats {
+/// and every resource pool in resource_names
+pub fn new_player_stats(players: Vec<AgentPubKeyB64>, resource_names: Vec<String>) -> PlayerStats {
+    let no_resources: ResourcePools = resource_names
+        .into_iter()
+        .map(|name| (name, Fixed::ZERO))
+        .collect();
     players
         .into_iter()
-        .map(move |pub_key| (pub_key, (0, 0)))
+        .map(move |pub_key| {
+            let stat = PlayerStat {
+                resources: no_resources.clone(),
+                ..PlayerStat::default()
+            };
+            (pub_key, stat)
+        })
         .collect::<PlayerStats>()
 }
@@ -30,6 +30,37 @@ pub fn try_get_game_moves(entry_hash: EntryHash) -> Vec<GameMove> {
     items
 }
 
+/// Follows the update chain of an entry and returns the element holding its most recent
+/// version. When an entry has several updates, the one with the latest timestamp wins.
+pub fn try_get_latest_element(entry_hash: EntryHash) -> ExternResult<Element> {
+    let mut current = entry_hash;
+    let mut visited: Vec<EntryHash> = vec![];
+    loop {
+        let latest_update = match get_details(current.clone(), GetOptions::latest())? {
+            Some(Details::Entry(details)) => details
+                .updates
+                .into_iter()
+                .max_by_key(|update| update.header().timestamp()),
+            _ => None,
+        };
+        let next = match latest_update.and_then(|u| u.header().entry_hash().cloned()) {
+            Some(next) => next,
+            None => break,
+        };
+        visited.push(current);
+        // an update can restore an earlier version, in that case we stop there
+        let seen = visited.contains(&next);
+        current = next;
+        if seen {
+            break;
+        }
+    }
+    match get(current, GetOptions::latest())? {
+        Some(element) => Ok(element),
+        None => Err(crate::err("Entry not found")),
+    }
+}
+
 pub fn try_from_element<T: TryFrom<Entry>>(element: Element) -> ExternResult<T> {
     match element.entry() {
         element::ElementEntry::Present(entry) => {
@@ -39,6 +70,12 @@ pub fn try_from_element<T: TryFrom<Entry>>(element: Element) -> ExternResult<T>
     }
 }
 
+/// Current system time as a Timestamp, for entries that have to record when they were made
+pub fn now() -> ExternResult<Timestamp> {
+    let time = sys_time()?;
+    Ok(Timestamp(time.as_secs() as i64, time.subsec_nanos()))
+}
+
 pub fn entry_hash_from_element(element:Element) -> ExternResult<EntryHash> {
     let hash = element.header().entry_hash();
     match hash {